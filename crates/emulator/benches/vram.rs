@@ -0,0 +1,112 @@
+//! Compares the original `[[bool; 64]; 32]` framebuffer against the packed one.
+//!
+//! Run with `cargo bench -p chipmunk-backend`
+
+use chipmunk_backend::{Vram, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+type BoolVram = [[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+
+// The same sprite drawn at a few positions, including ones that wrap
+const SPRITE: [u8; 15] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0,
+];
+const POSITIONS: [(u8, u8); 4] = [(0, 0), (30, 10), (60, 20), (63, 31)];
+
+/// DXYN as it was implemented before packing, pixel by pixel
+fn draw_bools(vram: &mut BoolVram, x: u8, y: u8, sprite: &[u8]) -> bool {
+    let mut collision = false;
+    for (yline, pixel) in sprite.iter().enumerate() {
+        for xline in 0..8 {
+            let is_on = (pixel & (0x80 >> xline)) != 0;
+            let y_idx = (y.wrapping_add(yline as u8)) as usize % DISPLAY_HEIGHT;
+            let x_idx = (x.wrapping_add(xline)) as usize % DISPLAY_WIDTH;
+
+            if is_on {
+                if vram[y_idx][x_idx] {
+                    collision = true;
+                }
+                vram[y_idx][x_idx] ^= true;
+            }
+        }
+    }
+    collision
+}
+
+// Presenting fills an RGB24 texture a pixel at a time, like the SDL2 display
+const BYTES_PER_PIXEL: usize = 3;
+const PITCH: usize = DISPLAY_WIDTH * BYTES_PER_PIXEL;
+const BACKGROUND: [u8; 3] = [0x00, 0x00, 0x00];
+const FOREGROUND: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+fn fill_pixel(buffer: &mut [u8], x: usize, y: usize, on: bool) {
+    let offset = y * PITCH + x * BYTES_PER_PIXEL;
+    let color = if on { FOREGROUND } else { BACKGROUND };
+    buffer[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&color);
+}
+
+fn present_bools(vram: &BoolVram, buffer: &mut [u8]) {
+    for (y, row) in vram.iter().enumerate() {
+        for (x, pixel) in row.iter().enumerate() {
+            fill_pixel(buffer, x, y, *pixel);
+        }
+    }
+}
+
+fn present_packed(vram: &Vram, buffer: &mut [u8]) {
+    for y in 0..vram.height() {
+        for x in 0..vram.width() {
+            fill_pixel(buffer, x, y, vram.pixel(x, y));
+        }
+    }
+}
+
+fn bench_dxyn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dxyn");
+
+    group.bench_function("before (bool array)", |b| {
+        let mut vram = [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        b.iter(|| {
+            for (x, y) in POSITIONS {
+                black_box(draw_bools(&mut vram, x, y, black_box(&SPRITE)));
+            }
+        })
+    });
+
+    group.bench_function("after (packed rows)", |b| {
+        let mut vram = Vram::new();
+        b.iter(|| {
+            for (x, y) in POSITIONS {
+                black_box(vram.draw_sprite(x as usize, y as usize, black_box(&SPRITE)));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_present(c: &mut Criterion) {
+    let mut group = c.benchmark_group("present");
+
+    // the same frame in both layouts
+    let mut bools = [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+    let mut packed = Vram::new();
+    for (x, y) in POSITIONS {
+        draw_bools(&mut bools, x, y, &SPRITE);
+        packed.draw_sprite(x as usize, y as usize, &SPRITE);
+    }
+    let mut buffer = vec![0; PITCH * DISPLAY_HEIGHT];
+
+    group.bench_function("before (bool array)", |b| {
+        b.iter(|| present_bools(black_box(&bools), &mut buffer))
+    });
+
+    group.bench_function("after (packed rows)", |b| {
+        b.iter(|| present_packed(black_box(&packed), &mut buffer))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_dxyn, bench_present);
+criterion_main!(benches);
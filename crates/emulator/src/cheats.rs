@@ -0,0 +1,247 @@
+use std::{fmt, str::FromStr};
+
+use crate::machine::{Machine, MEMORY_SIZE};
+use crate::registers::Reg;
+
+// Registers a cheat or a search can look at, the timers included
+const REGISTERS: [Reg; 18] = [
+    Reg::V0,
+    Reg::V1,
+    Reg::V2,
+    Reg::V3,
+    Reg::V4,
+    Reg::V5,
+    Reg::V6,
+    Reg::V7,
+    Reg::V8,
+    Reg::V9,
+    Reg::VA,
+    Reg::VB,
+    Reg::VC,
+    Reg::VD,
+    Reg::VE,
+    Reg::VF,
+    Reg::DelayTimer,
+    Reg::SoundTimer,
+];
+
+/// A byte of the machine: one of memory, or a register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Memory(u16),
+    Register(Reg),
+}
+
+impl Target {
+    /// Every byte there is, memory first
+    pub fn all() -> impl Iterator<Item = Target> {
+        (0..MEMORY_SIZE as u16)
+            .map(Target::Memory)
+            .chain(REGISTERS.into_iter().map(Target::Register))
+    }
+
+    pub fn read(self, machine: &Machine) -> u8 {
+        match self {
+            Target::Memory(address) => machine.memory()[address as usize],
+            Target::Register(reg) => machine.registers().get(reg),
+        }
+    }
+
+    pub fn write(self, machine: &mut Machine, value: u8) {
+        match self {
+            Target::Memory(address) => {
+                // parsing made sure the address is in memory
+                let _ = machine.poke(address, value);
+            }
+            Target::Register(reg) => machine.set_register(reg, value),
+        }
+    }
+}
+
+/// `M2F0` for memory, `V3` for a register, `DT` and `ST` for the timers
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Memory(address) => write!(f, "M{:03X}", address),
+            Target::Register(Reg::DelayTimer) => write!(f, "DT"),
+            Target::Register(Reg::SoundTimer) => write!(f, "ST"),
+            Target::Register(reg) => write!(f, "{:?}", reg),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_uppercase();
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+
+        match s.as_str() {
+            "DT" => Ok(Target::Register(Reg::DelayTimer)),
+            "ST" => Ok(Target::Register(Reg::SoundTimer)),
+            _ => {
+                if let Some(address) = s.strip_prefix('M').and_then(hex) {
+                    if (address as usize) < MEMORY_SIZE {
+                        return Ok(Target::Memory(address));
+                    }
+                } else if let Some(index) =
+                    s.strip_prefix('V').filter(|x| x.len() == 1).and_then(hex)
+                {
+                    return Ok(Target::Register((index as usize).into()));
+                }
+
+                Err(format!("expected M000-MFFF, V0-VF, DT or ST, got '{}'", s))
+            }
+        }
+    }
+}
+
+/// Keeps a byte at a value, set again every frame. Written as `TARGET=VALUE`
+/// in hex, like `M2F0=05` or `VA=FF`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub target: Target,
+    pub value: u8,
+}
+
+impl Cheat {
+    pub fn apply(self, machine: &mut Machine) {
+        self.target.write(machine, self.value);
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={:02X}", self.target, self.value)
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected a cheat like M2F0=05, got '{}'", s))?;
+        let value = u8::from_str_radix(value.trim(), 16)
+            .map_err(|_| format!("expected a hex byte after '=', got '{}'", value))?;
+
+        Ok(Cheat {
+            target: target.parse()?,
+            value,
+        })
+    }
+}
+
+/// How to narrow a search down, comparing with the last snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    fn keeps(self, before: u8, now: u8) -> bool {
+        match self {
+            Filter::Equal(value) => now == value,
+            Filter::Changed => now != before,
+            Filter::Unchanged => now == before,
+            Filter::Increased => now > before,
+            Filter::Decreased => now < before,
+        }
+    }
+}
+
+/// Finds where a game keeps something, like lives or a score, by taking
+/// snapshots of the machine and keeping the bytes that changed the way the
+/// thing did
+#[derive(Clone, Debug)]
+pub struct Search {
+    // what is left, with its value in the last snapshot
+    candidates: Vec<(Target, u8)>,
+}
+
+impl Search {
+    /// Start with every byte of `machine`
+    pub fn new(machine: &Machine) -> Self {
+        Self {
+            candidates: Target::all()
+                .map(|target| (target, target.read(machine)))
+                .collect(),
+        }
+    }
+
+    /// Keep the bytes `filter` holds for since the last snapshot, and take a
+    /// new one
+    pub fn narrow(&mut self, machine: &Machine, filter: Filter) {
+        self.candidates.retain_mut(|(target, before)| {
+            let now = target.read(machine);
+            let keep = filter.keeps(*before, now);
+            *before = now;
+            keep
+        });
+    }
+
+    /// What is left, with the values last seen
+    pub fn candidates(&self) -> &[(Target, u8)] {
+        &self.candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cheats::{Cheat, Filter, Search, Target};
+    use crate::machine::Machine;
+    use crate::registers::Reg;
+
+    #[test]
+    fn cheat_codes_round_trip() {
+        for code in ["M2F0=05", "VA=FF", "DT=00", "ST=3C"] {
+            let cheat: Cheat = code.parse().unwrap();
+            assert_eq!(cheat.to_string(), code);
+        }
+        assert_eq!(
+            "m300 = 7".parse::<Cheat>().unwrap(),
+            Cheat {
+                target: Target::Memory(0x300),
+                value: 7
+            }
+        );
+        assert!("M1000=00".parse::<Cheat>().is_err());
+        assert!("VG=00".parse::<Cheat>().is_err());
+        assert!("V1=100".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn searches_narrow_down_to_what_changed() {
+        let mut machine = Machine::new();
+        machine.poke(0x300, 3).unwrap();
+        machine.poke(0x301, 3).unwrap();
+        let mut search = Search::new(&machine);
+
+        // lose a life
+        machine.poke(0x300, 2).unwrap();
+        machine.set_register(Reg::V5, 9);
+        search.narrow(&machine, Filter::Decreased);
+        assert_eq!(search.candidates(), [(Target::Memory(0x300), 2)]);
+
+        search.narrow(&machine, Filter::Equal(2));
+        assert_eq!(search.candidates().len(), 1);
+        search.narrow(&machine, Filter::Changed);
+        assert!(search.candidates().is_empty());
+    }
+
+    #[test]
+    fn cheats_write_their_value() {
+        let mut machine = Machine::new();
+        "M300=09".parse::<Cheat>().unwrap().apply(&mut machine);
+        "V2=04".parse::<Cheat>().unwrap().apply(&mut machine);
+
+        assert_eq!(machine.memory()[0x300], 9);
+        assert_eq!(machine.registers().get(Reg::V2), 4);
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::RangeInclusive;
+
+use crate::machine::{Machine, MEMORY_SIZE};
+use crate::observer::Observer;
+
+/// How the rom touched a byte of memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// An instruction started here
+    Execute,
+    /// Read as data, by DXYN or FX65
+    Read,
+    /// Written by FX33 or FX55
+    Write,
+}
+
+impl Access {
+    pub const ALL: [Access; 3] = [Access::Execute, Access::Read, Access::Write];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::Execute => "executed",
+            Access::Read => "read",
+            Access::Write => "written",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        match self {
+            Access::Execute => 0b001,
+            Access::Read => 0b010,
+            Access::Write => 0b100,
+        }
+    }
+}
+
+/// Which addresses a run executed, read and wrote. Add it to an
+/// [`Emulator`](crate::Emulator) as an observer.
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    marks: [u8; MEMORY_SIZE],
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            marks: [0; MEMORY_SIZE],
+        }
+    }
+
+    fn mark(&mut self, address: u16, access: Access) {
+        if let Some(marks) = self.marks.get_mut(address as usize) {
+            *marks |= access.bit();
+        }
+    }
+
+    pub fn get(&self, address: u16, access: Access) -> bool {
+        self.marks
+            .get(address as usize)
+            .is_some_and(|marks| marks & access.bit() != 0)
+    }
+
+    /// How many addresses were touched in this way
+    pub fn count(&self, access: Access) -> usize {
+        self.marks
+            .iter()
+            .filter(|marks| *marks & access.bit() != 0)
+            .count()
+    }
+
+    /// Runs of neighbouring addresses that were touched in this way
+    pub fn ranges(&self, access: Access) -> Vec<RangeInclusive<u16>> {
+        let mut ranges: Vec<RangeInclusive<u16>> = Vec::new();
+        for address in 0..MEMORY_SIZE as u16 {
+            if !self.get(address, access) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if *range.end() + 1 == address => *range = *range.start()..=address,
+                _ => ranges.push(address..=address),
+            }
+        }
+        ranges
+    }
+}
+
+impl Observer for Coverage {
+    fn before_instruction(&mut self, _machine: &Machine, address: u16, _instruction: u16) {
+        self.mark(address, Access::Execute);
+    }
+
+    fn memory_read(&mut self, address: u16, _value: u8) {
+        self.mark(address, Access::Read);
+    }
+
+    fn memory_write(&mut self, address: u16, _old: u8, _new: u8) {
+        self.mark(address, Access::Write);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::coverage::{Access, Coverage};
+    use crate::{Emulator, Headless, Peripherals, Settings};
+
+    #[test]
+    fn records_code_data_and_writes() {
+        let rom = vec![
+            0xA2, 0x0A, // I = 0x20A
+            0xD0, 0x02, // draw the 2 bytes at I
+            0xF1, 0x55, // store V0 and V1 at I
+            0x12, 0x06, // spin
+            0x00, 0x00, // never run
+            0xFF, 0x81, // sprite
+        ];
+        let coverage = Rc::new(RefCell::new(Coverage::new()));
+        let mut emulator = Emulator::new(
+            rom,
+            Peripherals::from_platform(Headless::new(None)),
+            Settings::default(),
+            false,
+        )
+        .unwrap();
+        emulator.add_observer(Box::new(Rc::clone(&coverage)));
+        for _ in 0..5 {
+            emulator.step().unwrap();
+        }
+
+        let coverage = coverage.borrow();
+        assert_eq!(
+            coverage.ranges(Access::Execute),
+            [0x200..=0x200, 0x202..=0x202, 0x204..=0x204, 0x206..=0x206]
+        );
+        assert!(!coverage.get(0x208, Access::Execute));
+        assert_eq!(coverage.ranges(Access::Read), [0x20A..=0x20B]);
+        assert_eq!(coverage.ranges(Access::Write), [0x20A..=0x20B]);
+        assert_eq!(coverage.count(Access::Execute), 4);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt::{self, Write};
+
+use crate::cheats::Cheat;
+use crate::error::EmulatorError;
+use crate::machine::{Frame, Machine};
+use crate::opcode::mnemonic;
+use crate::quirks::Settings;
+use crate::registers::Reg;
+
+/// Told about a crash before the emulator gives up, see
+/// [`Emulator::set_crash_handler`](crate::Emulator::set_crash_handler)
+pub type CrashHandler = Box<dyn FnMut(&Crash)>;
+
+/// What the machine was like when the input movie of a [`Crash`] started
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingStart {
+    /// The rom just loaded into cleared memory
+    PowerOn,
+    /// The rom restarted with a reset, memory past it kept
+    Reset,
+    /// A save state loaded from this slot
+    State(usize),
+}
+
+impl fmt::Display for RecordingStart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordingStart::PowerOn => write!(f, "power on"),
+            RecordingStart::Reset => write!(f, "reset, memory past the rom kept"),
+            RecordingStart::State(slot) => write!(f, "save state {}", slot),
+        }
+    }
+}
+
+/// Everything the emulator knew when a rom stopped on an error, enough to run
+/// it into the same error again
+pub struct Crash {
+    pub error: EmulatorError,
+    /// Where the instruction that failed starts
+    pub address: u16,
+    pub machine: Machine,
+    pub rom: Vec<u8>,
+    pub settings: Settings,
+    /// What CXNN's random numbers came from
+    pub seed: u64,
+    /// The instructions run last, oldest first, as address and instruction
+    pub trace: Vec<(u16, u16)>,
+    /// Since when the frames below were recorded
+    pub start: RecordingStart,
+    /// How many frames ran since then
+    pub frames: usize,
+    /// The keypad on the first frames of those, bit N for key N
+    pub inputs: Vec<u16>,
+    /// Bytes changed from outside, as the frames run before, address and value
+    pub pokes: Vec<(usize, u16, u8)>,
+    /// Values held every frame
+    pub cheats: Vec<Cheat>,
+}
+
+impl Crash {
+    pub fn call_stack(&self) -> Vec<Frame> {
+        self.machine.call_stack(self.address)
+    }
+
+    /// The error, the registers, the call stack and the trace, as text
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        let registers = self.machine.registers();
+
+        let _ = writeln!(out, "error: {:?}", self.error);
+        let _ = writeln!(
+            out,
+            "at {:03X}: {:04X} {}",
+            self.address,
+            self.machine.instruction_at(self.address),
+            mnemonic(self.machine.instruction_at(self.address))
+        );
+        let _ = writeln!(out, "frames run: {}, since {}", self.frames, self.start);
+        if self.inputs.len() < self.frames {
+            let _ = writeln!(out, "inputs recorded for the first {}", self.inputs.len());
+        }
+
+        let _ = writeln!(out, "\nregisters:");
+        let _ = writeln!(
+            out,
+            "  PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}",
+            registers.pc(),
+            registers.get_i(),
+            registers.get(Reg::DelayTimer),
+            registers.get(Reg::SoundTimer)
+        );
+        for first in (0..16).step_by(8) {
+            let line: Vec<String> = (first..first + 8)
+                .map(|index: usize| format!("V{:X} {:02X}", index, registers.get(index.into())))
+                .collect();
+            let _ = writeln!(out, "  {}", line.join("  "));
+        }
+
+        let _ = writeln!(out, "\ncall stack, innermost first:");
+        for frame in self.call_stack() {
+            let _ = writeln!(out, "  {}", frame);
+        }
+
+        let _ = writeln!(
+            out,
+            "\nlast {} instructions, oldest first:",
+            self.trace.len()
+        );
+        for &(address, instruction) in &self.trace {
+            let _ = writeln!(
+                out,
+                "  {:03X}: {:04X} {}",
+                address,
+                instruction,
+                mnemonic(instruction)
+            );
+        }
+
+        out
+    }
+
+    /// The settings and seed to run the rom with again, as text
+    pub fn describe_settings(&self) -> String {
+        let mut out = String::new();
+        let quirks: Vec<&str> = self
+            .settings
+            .quirks
+            .enabled()
+            .map(|quirk| quirk.name())
+            .collect();
+
+        let _ = writeln!(out, "tickrate: {}", self.settings.tickrate);
+        let _ = writeln!(out, "quirks: {}", quirks.join(", "));
+        let _ = writeln!(
+            out,
+            "stack: {} deep{}",
+            self.settings.stack.depth,
+            if self.settings.stack.in_memory {
+                ", in memory"
+            } else {
+                ""
+            }
+        );
+        let _ = writeln!(out, "seed: {}", self.seed);
+        let _ = writeln!(out, "inputs from: {}", self.start);
+        let cheats: Vec<String> = self.cheats.iter().map(ToString::to_string).collect();
+        let _ = writeln!(out, "cheats: {}", cheats.join(" "));
+        for &(frame, address, value) in &self.pokes {
+            let _ = writeln!(
+                out,
+                "poke after {} frames: M{:03X}={:02X}",
+                frame, address, value
+            );
+        }
+        out
+    }
+
+    /// The input movie as text: a line per run of frames with the same keys
+    /// held, the number of frames then the keypad in hex
+    pub fn describe_inputs(&self) -> String {
+        let mut out = String::new();
+        let mut frames = self.inputs.iter().peekable();
+        while let Some(&keys) = frames.next() {
+            let mut count = 1;
+            while frames.next_if_eq(&&keys).is_some() {
+                count += 1;
+            }
+            let _ = writeln!(out, "{} {:04X}", count, keys);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crash::{Crash, RecordingStart};
+    use crate::error::EmulatorError;
+    use crate::machine::Machine;
+    use crate::quirks::Settings;
+
+    #[test]
+    fn inputs_are_written_as_runs_of_frames() {
+        let crash = Crash {
+            error: EmulatorError::StackUnderflow(0x200),
+            address: 0x200,
+            machine: Machine::new(),
+            rom: Vec::new(),
+            settings: Settings::default(),
+            seed: 7,
+            trace: Vec::new(),
+            start: RecordingStart::PowerOn,
+            frames: 6,
+            inputs: vec![0, 0, 0, 0x0010, 0x0010, 0],
+            pokes: vec![(4, 0x300, 0x0A)],
+            cheats: vec!["VA=09".parse().unwrap()],
+        };
+
+        assert_eq!(crash.describe_inputs(), "3 0000\n2 0010\n1 0000\n");
+        assert!(crash.describe_settings().ends_with(
+            "seed: 7\ninputs from: power on\ncheats: VA=09\npoke after 4 frames: M300=0A\n"
+        ));
+        assert!(crash
+            .describe()
+            .starts_with("error: StackUnderflow(512)\nat 200: 0000 "));
+    }
+}
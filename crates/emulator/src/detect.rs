@@ -0,0 +1,461 @@
+use crate::emulator::Emulator;
+use crate::error::EmulatorError;
+use crate::opcode::OpCode;
+use crate::quirks::{Quirk, Quirks, Settings, Stack};
+use crate::rom::{self, Variant};
+use crate::{Headless, Peripherals};
+
+// Five seconds of running without input, long enough to get past most title
+// screens
+const TRIAL_FRAMES: usize = 300;
+const TRIAL_SEED: u64 = 0xC8;
+// How far to look after FX55/FX65 for I being used again
+const LOOKAHEAD: usize = 16;
+
+/// A guess at one quirk, and how sure it is, from 0.0 to 1.0
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuirkGuess {
+    pub quirk: Quirk,
+    pub on: bool,
+    pub confidence: f32,
+    pub reason: String,
+}
+
+/// What [`detect_quirks`] made of a rom
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuirkReport {
+    pub variant: Variant,
+    pub guesses: Vec<QuirkGuess>,
+}
+
+impl QuirkReport {
+    /// The quirks of the guessed platform, with the guesses applied
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::for_variant(self.variant);
+        for guess in &self.guesses {
+            quirks.set(guess.quirk, guess.on);
+        }
+        quirks
+    }
+}
+
+fn decode(rom: &[u8], address: u16) -> Option<OpCode> {
+    rom::word_at(rom, address).and_then(|raw| raw.try_into().ok())
+}
+
+/// Registers an instruction writes to, one bit per register
+fn written_registers(opcode: &OpCode) -> u16 {
+    let bit = |reg| 1 << usize::from(reg);
+    match *opcode {
+        OpCode::_6XNN { reg, .. }
+        | OpCode::_7XNN { reg, .. }
+        | OpCode::CXNN { reg, .. }
+        | OpCode::FX07(reg)
+        | OpCode::FX0A(reg) => bit(reg),
+        OpCode::_8XY0 { x, .. }
+        | OpCode::_8XY1 { x, .. }
+        | OpCode::_8XY2 { x, .. }
+        | OpCode::_8XY3 { x, .. } => bit(x),
+        OpCode::_8XY4 { x, .. }
+        | OpCode::_8XY5 { x, .. }
+        | OpCode::_8XY6 { x, .. }
+        | OpCode::_8XY7 { x, .. }
+        | OpCode::_8XYE { x, .. } => bit(x) | 1 << 0xF,
+        OpCode::FX65(x) => (2 << usize::from(x)) - 1,
+        _ => 0,
+    }
+}
+
+/// Whether I is used again after the FX55/FX65 at `address` without being
+/// loaded first, going down the code in a straight line
+fn reuses_i(rom: &[u8], address: u16) -> bool {
+    let mut address = address.wrapping_add(2);
+    for _ in 0..LOOKAHEAD {
+        match decode(rom, address) {
+            Some(OpCode::ANNN(_) | OpCode::FX29(_)) => return false,
+            Some(
+                OpCode::DXYN { .. }
+                | OpCode::FX1E(_)
+                | OpCode::FX33(_)
+                | OpCode::FX55(_)
+                | OpCode::FX65(_),
+            ) => return true,
+            Some(OpCode::_1NNN(nnn)) => address = nnn,
+            // where these go, or what the subroutine does, is another question
+            Some(OpCode::_00EE | OpCode::_2NNN(_) | OpCode::BNNN(_)) | None => return false,
+            Some(_) => address = address.wrapping_add(2),
+        }
+    }
+
+    false
+}
+
+/// How trial runs with a quirk off and on went
+enum Trial {
+    /// Both drew the same, up to where they stopped
+    Same,
+    /// They drew differently from this frame, and both kept running
+    Differs(usize),
+    /// Only the run with the quirk at this setting kept running
+    Survives(bool),
+}
+
+/// Run `rom` with `quirk` off and on side by side, on the stack of `variant`,
+/// for [`TRIAL_FRAMES`] or until both stop on an error
+fn trial(rom: &[u8], variant: Variant, quirks: Quirks, quirk: Quirk) -> Trial {
+    let machines = [false, true].map(|on| {
+        let mut quirks = quirks;
+        quirks.set(quirk, on);
+        let settings = Settings {
+            quirks,
+            stack: Stack::for_variant(variant),
+            ..Settings::default()
+        };
+
+        let mut machine = Emulator::new(
+            rom.to_vec(),
+            Peripherals::from_platform(Headless::new(None)),
+            settings,
+            false,
+        )?;
+        machine.seed_rng(TRIAL_SEED);
+        Ok::<_, EmulatorError>(machine)
+    });
+    // too large to load
+    let [Ok(off), Ok(on)] = machines else {
+        return Trial::Same;
+    };
+    let mut machines = [off, on];
+
+    let mut stopped = [false; 2];
+    let mut difference = None;
+    for frame in 0..TRIAL_FRAMES {
+        for (machine, stopped) in machines.iter_mut().zip(&mut stopped) {
+            if !*stopped {
+                *stopped = machine.run_trial(1).is_err();
+            }
+        }
+        if difference.is_none()
+            && machines[0].machine().framebuffer() != machines[1].machine().framebuffer()
+        {
+            difference = Some(frame);
+        }
+        if stopped[0] && stopped[1] {
+            break;
+        }
+    }
+
+    match (stopped, difference) {
+        ([true, false], _) => Trial::Survives(true),
+        ([false, true], _) => Trial::Survives(false),
+        ([false, false], Some(frame)) => Trial::Differs(frame),
+        _ => Trial::Same,
+    }
+}
+
+struct Detector<'a> {
+    rom: &'a [u8],
+    variant: Variant,
+    preset: Quirks,
+    /// The reachable instructions, with their addresses
+    code: Vec<(u16, OpCode)>,
+    written: u16,
+}
+
+impl Detector<'_> {
+    fn guess(&self, quirk: Quirk, on: bool, confidence: f32, reason: &str) -> QuirkGuess {
+        QuirkGuess {
+            quirk,
+            on,
+            confidence,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Either value works, so keep the platform's
+    fn unused(&self, quirk: Quirk, reason: &str) -> QuirkGuess {
+        self.guess(quirk, self.preset.get(quirk), 1.0, reason)
+    }
+
+    fn is_written(&self, reg: usize) -> bool {
+        self.written & (1 << reg) != 0
+    }
+
+    fn shift(&self) -> QuirkGuess {
+        let uneven: Vec<(usize, usize)> = self
+            .code
+            .iter()
+            .filter_map(|(_, opcode)| match *opcode {
+                OpCode::_8XY6 { x, y } | OpCode::_8XYE { x, y } => {
+                    Some((usize::from(x), usize::from(y)))
+                }
+                _ => None,
+            })
+            .filter(|(x, y)| x != y)
+            .collect();
+
+        if uneven.is_empty() {
+            self.unused(Quirk::Shift, "every shift has X == Y, or there are none")
+        } else if uneven.iter().all(|(_, y)| !self.is_written(*y)) {
+            self.guess(
+                Quirk::Shift,
+                true,
+                0.8,
+                "shifts name a VY that is never set, so VX is shifted in place",
+            )
+        } else {
+            self.guess(
+                Quirk::Shift,
+                false,
+                0.6,
+                "shifts with X != Y read a VY that is set elsewhere",
+            )
+        }
+    }
+
+    fn memory(&self) -> [QuirkGuess; 2] {
+        let transfers: Vec<u16> = self
+            .code
+            .iter()
+            .filter(|(_, opcode)| matches!(opcode, OpCode::FX55(_) | OpCode::FX65(_)))
+            .map(|(address, _)| *address)
+            .collect();
+
+        let by_x = Quirk::MemoryIncrementByX;
+        let unchanged = Quirk::MemoryLeaveIUnchanged;
+        if transfers.is_empty() {
+            let reason = "there is no FX55 or FX65";
+            return [self.unused(by_x, reason), self.unused(unchanged, reason)];
+        }
+
+        if transfers.iter().any(|address| reuses_i(self.rom, *address)) {
+            let reason = "I is used again after FX55/FX65 without loading it, so it has to move";
+            [
+                self.guess(by_x, false, 0.6, reason),
+                self.guess(unchanged, false, 0.7, reason),
+            ]
+        } else {
+            let reason = "I is loaded again after every FX55/FX65";
+            [
+                self.guess(by_x, self.preset.memory_increment_by_x, 0.9, reason),
+                self.guess(unchanged, self.preset.memory_leave_i_unchanged, 0.9, reason),
+            ]
+        }
+    }
+
+    fn jump(&self) -> QuirkGuess {
+        let registers: Vec<usize> = self
+            .code
+            .iter()
+            .filter_map(|(_, opcode)| match opcode {
+                OpCode::BNNN(nnn) => Some((nnn >> 8) as usize),
+                _ => None,
+            })
+            .collect();
+
+        if registers.iter().all(|x| *x == 0) {
+            return self.unused(
+                Quirk::Jump,
+                "every BNNN would add V0 either way, or there are none",
+            );
+        }
+
+        let v0 = self.is_written(0);
+        let vx = registers.iter().any(|x| self.is_written(*x));
+        match (v0, vx) {
+            (false, true) => self.guess(Quirk::Jump, true, 0.8, "V0 is never set, VX is"),
+            (true, false) => self.guess(Quirk::Jump, false, 0.8, "VX is never set, V0 is"),
+            _ => self.guess(
+                Quirk::Jump,
+                self.preset.jump,
+                0.5,
+                "both V0 and VX are set, going by the platform",
+            ),
+        }
+    }
+
+    fn vblank(&self) -> QuirkGuess {
+        let draws = self
+            .code
+            .iter()
+            .any(|(_, opcode)| matches!(opcode, OpCode::DXYN { .. }));
+        let timed = self
+            .code
+            .iter()
+            .any(|(_, opcode)| matches!(opcode, OpCode::FX07(_)));
+
+        let on = self.preset.vblank;
+        match (draws, timed) {
+            (false, _) => self.unused(Quirk::Vblank, "nothing is drawn"),
+            (true, true) => self.guess(
+                Quirk::Vblank,
+                on,
+                0.7,
+                "the delay timer paces the game, so waiting only slows drawing; the platform \
+                 default",
+            ),
+            (true, false) => self.guess(
+                Quirk::Vblank,
+                on,
+                0.5,
+                "nothing in the code tells, so the platform default",
+            ),
+        }
+    }
+
+    /// Quirks that can only be told apart by what ends up on screen
+    fn by_trial(&self, quirk: Quirk, used: bool) -> QuirkGuess {
+        if !used {
+            return self.unused(quirk, "no instruction it changes is reached");
+        }
+
+        let on = self.preset.get(quirk);
+        match trial(self.rom, self.variant, self.preset, quirk) {
+            Trial::Survives(on) => self.guess(
+                quirk,
+                on,
+                0.7,
+                "a trial run with the other setting stops on an error",
+            ),
+            Trial::Differs(frame) => QuirkGuess {
+                quirk,
+                on,
+                confidence: 0.5,
+                reason: format!(
+                    "a trial run draws differently from frame {}, but nothing tells which is \
+                     right, so the platform default",
+                    frame
+                ),
+            },
+            Trial::Same => self.guess(quirk, on, 0.8, "a trial run draws the same either way"),
+        }
+    }
+}
+
+/// Guess the quirks `rom` needs from its reachable instructions, and for the
+/// wrap and logic quirks, from a short headless trial run with each setting:
+/// when only one of them keeps running, that one. Anything the code and the
+/// trials can't tell apart, vblank included, keeps the default of the platform
+/// [`rom::detect_variant`] picks.
+pub fn detect_quirks(rom: &[u8]) -> QuirkReport {
+    let variant = rom::detect_variant(rom);
+    let code: Vec<(u16, OpCode)> = rom::reachable_instructions(rom)
+        .into_iter()
+        .filter_map(|address| decode(rom, address).map(|opcode| (address, opcode)))
+        .collect();
+    let written = code.iter().fold(0, |written, (_, opcode)| {
+        written | written_registers(opcode)
+    });
+
+    let detector = Detector {
+        rom,
+        variant,
+        preset: Quirks::for_variant(variant),
+        code,
+        written,
+    };
+    let draws = detector
+        .code
+        .iter()
+        .any(|(_, opcode)| matches!(opcode, OpCode::DXYN { .. }));
+    let logic = detector.code.iter().any(|(_, opcode)| {
+        matches!(
+            opcode,
+            OpCode::_8XY1 { .. } | OpCode::_8XY2 { .. } | OpCode::_8XY3 { .. }
+        )
+    });
+
+    let [by_x, unchanged] = detector.memory();
+    QuirkReport {
+        variant,
+        guesses: vec![
+            detector.shift(),
+            by_x,
+            unchanged,
+            detector.by_trial(Quirk::Wrap, draws),
+            detector.jump(),
+            detector.vblank(),
+            detector.by_trial(Quirk::Logic, logic),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::detect::detect_quirks;
+    use crate::quirks::{Quirk, Quirks};
+    use crate::rom::Variant;
+
+    fn guess(rom: &[u8], quirk: Quirk) -> (bool, f32) {
+        let report = detect_quirks(rom);
+        let guess = report
+            .guesses
+            .iter()
+            .find(|guess| guess.quirk == quirk)
+            .unwrap();
+        (guess.on, guess.confidence)
+    }
+
+    #[test]
+    fn roms_without_the_instructions_keep_the_platform_quirks() {
+        // spin forever
+        let report = detect_quirks(&[0x12, 0x00]);
+
+        assert_eq!(report.variant, Variant::Chip8);
+        assert_eq!(report.quirks(), Quirks::for_variant(Variant::Chip8));
+        assert!(report.guesses.iter().all(|guess| guess.confidence == 1.0));
+    }
+
+    #[test]
+    fn shifting_a_register_that_is_never_set_means_in_place() {
+        // V1 >>= V0, spin
+        assert_eq!(guess(&[0x81, 0x06, 0x12, 0x02], Quirk::Shift), (true, 0.8));
+        // V0 = 4, V1 = V0 >> 1, spin
+        let rom = [0x60, 0x04, 0x81, 0x06, 0x12, 0x04];
+        assert_eq!(guess(&rom, Quirk::Shift), (false, 0.6));
+    }
+
+    #[test]
+    fn reusing_i_after_a_load_means_it_moves() {
+        // I = 300, load V0..=V1, load V0..=V1 again, spin
+        let rom = [0xA3, 0x00, 0xF1, 0x65, 0xF1, 0x65, 0x12, 0x06];
+        assert_eq!(guess(&rom, Quirk::MemoryLeaveIUnchanged), (false, 0.7));
+
+        // the same, loading I in between
+        let rom = [0xA3, 0x00, 0xF1, 0x65, 0xA3, 0x02, 0xF1, 0x65, 0x12, 0x08];
+        assert_eq!(guess(&rom, Quirk::MemoryLeaveIUnchanged), (false, 0.9));
+    }
+
+    #[test]
+    fn sprites_across_the_edge_show_up_in_the_trial() {
+        // V0 = 60, I = font 0, draw at (V0, V0), spin
+        let rom = [0x60, 0x3C, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x06];
+        assert_eq!(guess(&rom, Quirk::Wrap), (false, 0.5));
+
+        // drawn at (0, 0) instead
+        let rom = [0x60, 0x00, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x06];
+        assert_eq!(guess(&rom, Quirk::Wrap), (false, 0.8));
+    }
+
+    #[test]
+    fn the_setting_a_trial_survives_with_wins() {
+        // draw the font's 0 at (0, 0) and again at (62, 0), which only collides
+        // when wrapping, then return from nothing unless it collided
+        let rom = [
+            0xA0, 0x50, 0x60, 0x00, 0x61, 0x00, 0xD0, 0x15, 0x60, 0x3E, 0xD0, 0x15, 0x3F, 0x01,
+            0x00, 0xEE, 0x12, 0x10,
+        ];
+        assert_eq!(guess(&rom, Quirk::Wrap), (true, 0.7));
+    }
+
+    #[test]
+    fn trials_that_stop_on_an_error_still_get_guesses() {
+        // V0 = 0x20, I = its font character, which there is none of
+        let rom = [0x60, 0x20, 0xF0, 0x29, 0xD0, 0x01, 0x12, 0x06];
+        assert_eq!(guess(&rom, Quirk::Wrap), (false, 0.8));
+
+        // I = FF8, draw 15 rows from there, past the end of memory
+        let rom = [0xAF, 0xF8, 0xD0, 0x0F, 0x12, 0x04];
+        assert_eq!(guess(&rom, Quirk::Wrap), (false, 0.8));
+    }
+}
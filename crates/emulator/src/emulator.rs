@@ -1,13 +1,34 @@
-use rand::Rng;
-use std::time::Duration;
-
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{BTreeSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::cheats::Cheat;
+use crate::crash::{Crash, CrashHandler, RecordingStart};
+use crate::machine::{Machine, MEMORY_SIZE};
+use crate::observer::Observer;
+use crate::platform::{Audio, Display, HostCommand, HostControl, Input, Peripherals};
+use crate::quirks::Settings;
 use crate::registers::Reg;
+use crate::rom;
+use crate::strict::Strict;
 use crate::utils::bcd;
 use crate::{error::EmulatorError, opcode::OpCode, registers::Registers};
-use crate::{Platform, Vram, DISPLAY_HEIGHT, DISPLAY_WIDTH};
 
-const STACK_COUNT: usize = 12;
-const MEM_SIZE: usize = 4096;
+// Roms get loaded starting at memory location 0x200
+const ROM_START: usize = rom::ROM_START as usize;
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - ROM_START;
+
+// Timers count down, and the screen is presented, at 60Hz
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// How much faster or slower fast forward and slow motion run
+const SPEED_FACTOR: u32 = 4;
+
+pub const STATE_SLOTS: usize = 10;
+
+// Instructions kept for crash reports
+const TRACE_LENGTH: usize = 64;
+// Frames of input kept for crash reports, an hour's worth
+const MAX_RECORDED_FRAMES: usize = 60 * 60 * 60;
 
 const FONT_SET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -31,119 +52,586 @@ const FONT_SET: [u8; 80] = [
 const FONT_SET_START_ADDR: usize = 0x050;
 const FONT_SET_END_ADDR: usize = 0x0A0;
 
+// With the stack in memory, return addresses go down from here like on the VIP
+const MEMORY_STACK_END: usize = 0xED0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Speed {
+    Normal,
+    FastForward,
+    SlowMotion,
+}
+
+impl Speed {
+    fn frame_duration(self) -> Duration {
+        match self {
+            Speed::Normal => FRAME_DURATION,
+            Speed::FastForward => FRAME_DURATION / SPEED_FACTOR,
+            Speed::SlowMotion => FRAME_DURATION * SPEED_FACTOR,
+        }
+    }
+
+    /// Switch to `speed`, or back to normal if already there
+    fn toggle(self, speed: Speed) -> Speed {
+        if self == speed {
+            Speed::Normal
+        } else {
+            speed
+        }
+    }
+}
+
 pub struct Emulator {
-    platform: Box<dyn Platform>,
-    // 0x000 -> 0x1FF = interpter
-    // 0x050 -> 0x0A0 = pixel font
-    // 0x200 -> 0xFFF = rom and everything else
-    memory: [u8; MEM_SIZE], // 4 KB of memory that lives for the entire program
-    pub registers: Registers,
-    stacks: Vec<u16>,
-    vram: Vram,
-    draw_flag: bool,
+    display: Box<dyn Display>,
+    audio: Box<dyn Audio>,
+    input: Box<dyn Input>,
+    host: Box<dyn HostControl>,
+    machine: Machine,
+    beeping: bool,
+    // kept around for resets
+    rom: Vec<u8>,
+
+    paused: bool,
+    // run a single frame while paused
+    advance: bool,
+    speed: Speed,
+    states: [Option<Box<Machine>>; STATE_SLOTS],
+
+    settings: Settings,
+    // set by a draw with the vblank quirk, ends the frame early
+    waiting_for_vblank: bool,
+    // for CXNN, seeded when runs have to be repeatable
+    rng: StdRng,
+    seed: u64,
+    // the key an FX0A saw pressed, it completes when the key is released
+    awaited_key: Option<u8>,
+    observers: Vec<Box<dyn Observer>>,
+    breakpoints: BTreeSet<u16>,
+    // the breakpoint paused at, run past once when carrying on
+    resume_from: Option<u16>,
+    cheats: Vec<Cheat>,
+    strict: Option<Strict>,
+    // where the instruction being run starts, for call stack dumps
+    current: u16,
+    // what led up to a crash: the last instructions, and since the recording
+    // started, the keypad every frame and the bytes poked
+    trace: VecDeque<(u16, u16)>,
+    recording_start: RecordingStart,
+    frames: usize,
+    inputs: Vec<u16>,
+    pokes: Vec<(usize, u16, u8)>,
+    crash_handler: Option<CrashHandler>,
 
     // Debug mode will wait each cycle for "f" to be pressed before continuing
     debug: bool,
 }
 
 impl Emulator {
-    pub fn new(rom: Vec<u8>, platform: Box<dyn Platform>, debug: bool) -> Self {
-        let memory: [u8; MEM_SIZE] = [0; MEM_SIZE];
-        let registers = Registers::new();
-        let vram: Vram = [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+    /// A machine with `rom` loaded, which has to fit in memory
+    pub fn new(
+        rom: Vec<u8>,
+        peripherals: Peripherals,
+        settings: Settings,
+        debug: bool,
+    ) -> Result<Self, EmulatorError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(EmulatorError::RomTooLarge(rom.len()));
+        }
+
+        let Peripherals {
+            display,
+            audio,
+            input,
+            host,
+        } = peripherals;
 
+        let seed = rand::random();
         let mut emulator = Self {
-            platform,
-            memory,
-            registers,
-            stacks: Vec::with_capacity(STACK_COUNT),
-            vram,
-            draw_flag: false,
+            display,
+            audio,
+            input,
+            host,
+            machine: Machine::new(),
+            beeping: false,
+            rom,
+            paused: false,
+            advance: false,
+            speed: Speed::Normal,
+            states: Default::default(),
+            settings,
+            waiting_for_vblank: false,
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+            awaited_key: None,
+            observers: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            resume_from: None,
+            cheats: Vec::new(),
+            strict: None,
+            current: ROM_START as u16,
+            trace: VecDeque::with_capacity(TRACE_LENGTH),
+            recording_start: RecordingStart::PowerOn,
+            frames: 0,
+            inputs: Vec::new(),
+            pokes: Vec::new(),
+            crash_handler: None,
             debug,
         };
 
         emulator.load_font();
-        emulator.load_rom(rom);
+        emulator.write_rom();
 
-        emulator
+        Ok(emulator)
     }
 
     fn load_font(&mut self) {
         let mut i = FONT_SET_START_ADDR;
         while i < FONT_SET_END_ADDR {
-            self.memory[i] = FONT_SET[i - FONT_SET_START_ADDR];
+            self.machine.memory[i] = FONT_SET[i - FONT_SET_START_ADDR];
             i += 1;
         }
     }
 
-    fn load_rom(&mut self, rom: Vec<u8>) {
-        // Roms get loaded starting at memory location 0x200
-        let mut idx: usize = 0x200;
-        for byte in rom.into_iter() {
-            *self.memory.get_mut(idx).expect("rom went over 4k") = byte;
+    fn write_rom(&mut self) {
+        self.machine.memory[ROM_START..ROM_START + self.rom.len()].copy_from_slice(&self.rom);
+    }
 
-            idx += 1;
+    /// Start recording what leads up to a crash over, from `start`. The random
+    /// numbers start over too, so the recording can be run again.
+    fn restart_recording(&mut self, start: RecordingStart) {
+        self.recording_start = start;
+        self.frames = 0;
+        self.trace.clear();
+        self.inputs.clear();
+        self.pokes.clear();
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    /// Restart the rom, like pressing reset on the machine. Registers, stack, vram
+    /// and timers are cleared, and the rom image and font are loaded again. The
+    /// rest of memory keeps its contents.
+    pub fn reset(&mut self) {
+        self.restart();
+        self.restart_recording(RecordingStart::Reset);
+    }
+
+    fn restart(&mut self) {
+        self.silence();
+
+        self.machine.registers = Registers::new();
+        self.machine.stack.clear();
+        self.machine.vram.clear();
+        self.awaited_key = None;
+        self.resume_from = None;
+
+        self.load_font();
+        self.write_rom();
+        self.reset_strict();
+    }
+
+    /// Restart the rom with all of memory cleared, like turning the machine off
+    /// and on again
+    pub fn hard_reset(&mut self) {
+        self.machine.memory = [0; MEMORY_SIZE];
+        self.restart();
+        self.restart_recording(RecordingStart::PowerOn);
+    }
+
+    /// Switch to another rom, starting from a hard reset. Save states belong to
+    /// the previous rom, so they are dropped.
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), EmulatorError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(EmulatorError::RomTooLarge(rom.len()));
         }
+
+        self.rom = rom;
+        self.states = Default::default();
+        // addresses and values that meant something in the old rom
+        self.cheats.clear();
+        self.breakpoints.clear();
+        self.hard_reset();
+
+        Ok(())
     }
 
     pub fn start(&mut self) {
         'running: loop {
-            self.platform.scan_keys();
+            let frame_start = Instant::now();
 
-            if let Err(e) = self.cycle() {
-                // don't leave audio on before we panic
-                self.platform.stop_beep();
+            self.input.scan_keys();
+            while let Some(command) = self.host.next_command() {
+                self.handle_command(command);
+            }
+
+            let running = !self.paused || std::mem::take(&mut self.advance);
+
+            if running {
+                let keys = (0..16)
+                    .filter(|key| self.input.key_is_pressed(key))
+                    .fold(0, |keys, key| keys | 1 << key);
+                self.frames += 1;
+                if self.inputs.len() < MAX_RECORDED_FRAMES {
+                    self.inputs.push(keys);
+                }
+
+                for cheat in &self.cheats {
+                    cheat.apply(&mut self.machine);
+                }
+                match self.run_cycles() {
+                    Ok(()) => {}
+                    // asked to quit while waiting for a key in debug mode
+                    Err(EmulatorError::Exit) => {
+                        self.audio.stop_beep();
+                        break 'running;
+                    }
+                    Err(e) => {
+                        // don't leave audio on after stopping
+                        self.audio.stop_beep();
+
+                        let crash = self.crash(e);
+                        eprintln!("call stack, innermost first:");
+                        for frame in crash.call_stack() {
+                            eprintln!("  {}", frame);
+                        }
+                        if let Some(handler) = &mut self.crash_handler {
+                            handler(&crash);
+                        }
 
-                panic!("Ran into error: {:#?}", e);
+                        match &crash.error {
+                            EmulatorError::Strict(problem) => {
+                                eprintln!("strict mode stopped the rom at {}", problem)
+                            }
+                            error => eprintln!("stopped on error: {:?}", error),
+                        }
+                        break 'running;
+                    }
+                }
             }
 
-            if self.platform.should_quit() {
+            if self.host.should_quit() {
                 break 'running;
             }
 
-            std::thread::sleep(Duration::from_millis(2));
-            if self.draw_flag {
-                self.platform.draw(self.vram);
-                self.draw_flag = false;
+            // Present every frame rather than only after a draw, so frontends that
+            // smooth over frames (see `Phosphor`) keep animating
+            self.display.inspect(&self.machine);
+            self.display.draw(&self.machine.vram);
+
+            if running {
+                // sound timer
+                self.check_sound();
+                // delay timer
+                self.check_delay();
+
+                self.audio.end_frame();
+            }
+
+            if !self.host.throttle() {
+                continue;
+            }
+            let frame_duration = self.speed.frame_duration();
+            if let Some(remaining) = frame_duration.checked_sub(frame_start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+
+    /// Execute one frame's worth of instructions
+    fn run_cycles(&mut self) -> Result<(), EmulatorError> {
+        let mut resuming = self.resume_from.take();
+        for _ in 0..self.settings.tickrate {
+            let pc = self.machine.registers.pc();
+            if resuming.take() != Some(pc) && self.breakpoints.contains(&pc) {
+                self.paused = true;
+                self.resume_from = Some(pc);
+                self.silence();
+                println!("breakpoint at {:03X}", pc);
+                break;
+            }
+
+            self.cycle()?;
+
+            if std::mem::take(&mut self.waiting_for_vblank) {
+                break;
             }
+        }
+
+        Ok(())
+    }
+
+    /// Make the random numbers repeatable, for trial runs
+    pub(crate) fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.seed = seed;
+    }
 
-            // sound timer
+    /// Have `handler` told everything there is to know when the rom stops on an
+    /// error, before the emulator gives up
+    pub fn set_crash_handler(&mut self, handler: CrashHandler) {
+        self.crash_handler = Some(handler);
+    }
+
+    fn crash(&self, error: EmulatorError) -> Crash {
+        Crash {
+            error,
+            address: self.current,
+            machine: self.machine.clone(),
+            rom: self.rom.clone(),
+            settings: self.settings,
+            seed: self.seed,
+            trace: self.trace.iter().copied().collect(),
+            start: self.recording_start,
+            frames: self.frames,
+            inputs: self.inputs.clone(),
+            pokes: self.pokes.clone(),
+            cheats: self.cheats.clone(),
+        }
+    }
+
+    /// Run `frames` frames as fast as possible, without presenting them or asking
+    /// the host anything
+    pub(crate) fn run_trial(&mut self, frames: usize) -> Result<(), EmulatorError> {
+        for _ in 0..frames {
+            self.input.scan_keys();
+            self.run_cycles()?;
             self.check_sound();
-            // delay timer
             self.check_delay();
         }
+
+        Ok(())
+    }
+
+    /// Memory, registers, stack and screen of the emulated machine
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    /// Execute a single instruction, for stepping through a rom from outside
+    pub fn step(&mut self) -> Result<(), EmulatorError> {
+        self.cycle()
+    }
+
+    /// Keep these bytes at their values, set again at the start of every frame
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) {
+        self.cheats = cheats;
+    }
+
+    /// Stop with an [`EmulatorError::Strict`] when the rom does something that
+    /// only works by luck on some interpreters
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict.then(Strict::new);
+        self.reset_strict();
+    }
+
+    /// Have strict mode start over, knowing only the font and the rom image
+    fn reset_strict(&mut self) {
+        if let Some(strict) = &mut self.strict {
+            strict.reset(&[
+                (FONT_SET_START_ADDR, FONT_SET.len()),
+                (ROM_START, self.rom.len()),
+            ]);
+        }
+    }
+
+    fn strict_read(&mut self, start: usize, len: usize) -> Result<(), EmulatorError> {
+        match &mut self.strict {
+            Some(strict) => strict.read(start, len),
+            None => Ok(()),
+        }
+    }
+
+    fn strict_write(&mut self, start: usize, len: usize) -> Result<(), EmulatorError> {
+        match &mut self.strict {
+            Some(strict) => strict.write(start, len),
+            None => Ok(()),
+        }
+    }
+
+    /// Check where I was left by the instruction that just ran
+    fn strict_index(&self) -> Result<(), EmulatorError> {
+        match &self.strict {
+            Some(strict) => strict.index(self.machine.registers.get_i() as usize),
+            None => Ok(()),
+        }
+    }
+
+    /// Have `observer` told about everything the rom does from now on
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    fn notify(&mut self, mut event: impl FnMut(&mut dyn Observer, &Machine)) {
+        for observer in &mut self.observers {
+            event(observer.as_mut(), &self.machine);
+        }
+    }
+
+    /// Write to memory on behalf of the rom
+    fn write_memory(&mut self, address: usize, value: u8) {
+        let old = self.machine.memory[address];
+        self.machine.memory[address] = value;
+        self.notify(|observer, _| observer.memory_write(address as u16, old, value));
+    }
+
+    /// Tell the observers about the rom reading `len` bytes from `start`
+    fn observe_reads(&mut self, start: usize, len: usize) {
+        if self.observers.is_empty() {
+            return;
+        }
+        for address in start..start + len {
+            let value = self.machine.memory[address];
+            self.notify(|observer, _| observer.memory_read(address as u16, value));
+        }
+    }
+
+    fn key_is_pressed(&mut self, key: u8) -> bool {
+        let pressed = self.input.key_is_pressed(&key);
+        self.notify(|observer, _| observer.key_query(key, pressed));
+        pressed
+    }
+
+    fn handle_command(&mut self, command: HostCommand) {
+        match command {
+            HostCommand::TogglePause => {
+                self.paused = !self.paused;
+                if self.paused {
+                    self.silence();
+                }
+                println!("{}", if self.paused { "paused" } else { "resumed" });
+            }
+            HostCommand::FrameAdvance => {
+                if self.paused {
+                    self.advance = true;
+                } else {
+                    self.paused = true;
+                    self.silence();
+                    println!("paused");
+                }
+            }
+            HostCommand::Reset => {
+                self.reset();
+                println!("reset");
+            }
+            HostCommand::HardReset => {
+                self.hard_reset();
+                println!("hard reset");
+            }
+            HostCommand::LoadRom { rom, settings } => match self.load_rom(rom) {
+                Ok(()) => {
+                    self.settings = settings;
+                    println!("loaded rom");
+                }
+                Err(e) => eprintln!("failed to load rom: {:?}", e),
+            },
+            HostCommand::ToggleFastForward => {
+                self.speed = self.speed.toggle(Speed::FastForward);
+                println!("speed: {:?}", self.speed);
+            }
+            HostCommand::ToggleSlowMotion => {
+                self.speed = self.speed.toggle(Speed::SlowMotion);
+                println!("speed: {:?}", self.speed);
+            }
+            HostCommand::SaveState(slot) => {
+                let Some(state) = self.states.get_mut(slot) else {
+                    eprintln!("there is no state slot {}", slot);
+                    return;
+                };
+
+                *state = Some(Box::new(self.machine.clone()));
+                println!("saved state {}", slot);
+            }
+            HostCommand::LoadState(slot) => {
+                let Some(state) = self.states.get(slot).and_then(|state| state.as_deref()) else {
+                    eprintln!("state slot {} is empty", slot);
+                    return;
+                };
+
+                self.machine = state.clone();
+                self.awaited_key = None;
+                self.resume_from = None;
+                self.restart_recording(RecordingStart::State(slot));
+
+                // the beep picks back up from the restored sound timer
+                self.silence();
+                println!("loaded state {}", slot);
+            }
+            HostCommand::ToggleBreakpoint(address) => {
+                if !self.breakpoints.remove(&address) {
+                    self.breakpoints.insert(address);
+                }
+            }
+            HostCommand::Poke { address, value } => match self.machine.poke(address, value) {
+                Ok(()) => self.pokes.push((self.frames, address, value)),
+                Err(e) => eprintln!("failed to change memory: {:?}", e),
+            },
+            HostCommand::SetCheats(cheats) => self.set_cheats(cheats),
+        }
+    }
+
+    /// Stop the beep, if it is on
+    fn silence(&mut self) {
+        if self.beeping {
+            self.audio.stop_beep();
+            self.beeping = false;
+        }
     }
 
     // TODO - better name
     fn check_sound(&mut self) {
-        let sound_timer = self.registers.get(Reg::SoundTimer);
+        let sound_timer = self.machine.registers.get(Reg::SoundTimer);
 
+        // The tone plays for as long as the timer is non-zero
         if sound_timer > 0 {
-            if sound_timer == 1 {
-                self.platform.start_beep();
+            if !self.beeping {
+                self.audio.start_beep();
+                self.beeping = true;
             }
-            self.registers.set(Reg::SoundTimer, sound_timer - 1);
-        } else {
-            self.platform.stop_beep();
+            self.machine.registers.set(Reg::SoundTimer, sound_timer - 1);
+        } else if self.beeping {
+            self.audio.stop_beep();
+            self.beeping = false;
         }
     }
 
     fn check_delay(&mut self) {
-        let delay_timer = self.registers.get(Reg::DelayTimer);
+        let delay_timer = self.machine.registers.get(Reg::DelayTimer);
 
         if delay_timer > 0 {
-            self.registers.set(Reg::DelayTimer, delay_timer - 1);
+            self.machine.registers.set(Reg::DelayTimer, delay_timer - 1);
         }
     }
 
     fn cycle(&mut self) -> Result<(), EmulatorError> {
+        let address = self.machine.registers.pc();
+        self.current = address;
+        if self.trace.len() == TRACE_LENGTH {
+            self.trace.pop_front();
+        }
+        self.trace
+            .push_back((address, self.machine.instruction_at(address)));
+        self.notify(|observer, machine| {
+            observer.before_instruction(machine, address, machine.instruction_at(address))
+        });
+        if let Some(strict) = &mut self.strict {
+            strict.execute(address, self.machine.instruction_at(address))?;
+        }
         let opcode = self.fetch_opcode()?;
-        println!("{:#?}", opcode);
+
+        if self.debug {
+            println!("{:#?}", opcode);
+        }
         self.exec_opcode(opcode)?;
+        self.notify(|observer, machine| {
+            observer.after_instruction(machine, address, machine.instruction_at(address))
+        });
 
         if self.debug {
             loop {
-                match self.platform.await_keypress() {
+                match self.input.await_keypress() {
                     Ok(0xf) => break,
                     Err(e) => {
                         println!("{:#?}", e);
@@ -153,21 +641,22 @@ impl Emulator {
                 }
             }
 
-            println!("{:#?}", self.registers);
+            println!("{:#?}", self.machine.registers);
         }
 
         Ok(())
     }
 
     fn fetch_opcode(&mut self) -> Result<OpCode, EmulatorError> {
-        let index = self.registers.pc() as usize;
-        let first_half = self.memory[index];
-        let second_half = self.memory[index + 0x1];
+        let index = self.machine.registers.pc() as usize;
+        Self::check_range(index, 2)?;
+        let first_half = self.machine.memory[index];
+        let second_half = self.machine.memory[index + 0x1];
         let raw_opcode: u16 = u16::from_be_bytes([first_half, second_half]);
 
         let opcode = raw_opcode.try_into();
 
-        self.registers.advance_pc();
+        self.machine.registers.advance_pc();
 
         opcode
     }
@@ -175,261 +664,1142 @@ impl Emulator {
     fn exec_opcode(&mut self, op: OpCode) -> Result<(), EmulatorError> {
         match op {
             OpCode::_00E0 => {
-                for row in self.vram.iter_mut() {
-                    for pixel in row.iter_mut() {
-                        *pixel = false;
-                    }
-                }
-                self.draw_flag = true;
+                self.machine.vram.clear();
             }
             OpCode::_00EE => {
-                let ret_address = self.stacks.pop().expect("Must return from a subroutine");
-                self.registers.goto(ret_address);
+                let ret_address = self.pop_return()?;
+                let from = self.machine.registers.pc() - 2;
+                self.machine.registers.goto(ret_address);
+                self.notify(|observer, _| observer.ret(from, ret_address));
             }
-            OpCode::_1NNN(nnn) => self.registers.goto(nnn),
+            OpCode::_1NNN(nnn) => self.machine.registers.goto(nnn),
             OpCode::_2NNN(nnn) => {
-                self.stacks.push(self.registers.pc());
+                let ret_address = self.machine.registers.pc();
+                self.push_return(ret_address)?;
 
-                self.registers.goto(nnn)
+                self.machine.registers.goto(nnn);
+                self.notify(|observer, _| observer.call(ret_address - 2, nnn));
             }
             OpCode::_3XNN { reg, value } => {
-                let x = self.registers.get(reg);
+                let x = self.machine.registers.get(reg);
                 if x == value {
-                    self.registers.advance_pc();
+                    self.machine.registers.advance_pc();
                 }
             }
             OpCode::_4XNN { reg, value } => {
-                let x = self.registers.get(reg);
+                let x = self.machine.registers.get(reg);
                 if x != value {
-                    self.registers.advance_pc();
+                    self.machine.registers.advance_pc();
                 }
             }
             OpCode::_5XY0 { x, y } => {
-                let x = self.registers.get(x);
-                let y = self.registers.get(y);
+                let x = self.machine.registers.get(x);
+                let y = self.machine.registers.get(y);
                 if x == y {
-                    self.registers.advance_pc();
+                    self.machine.registers.advance_pc();
                 }
             }
-            OpCode::_6XNN { reg, value } => self.registers.set(reg, value),
+            OpCode::_6XNN { reg, value } => self.machine.registers.set(reg, value),
             OpCode::_7XNN { reg, value } => {
-                let original = self.registers.get(reg);
+                let original = self.machine.registers.get(reg);
                 let new = original.wrapping_add(value);
-                self.registers.set(reg, new)
+                self.machine.registers.set(reg, new)
             }
             OpCode::_8XY0 { x, y } => {
-                let y = self.registers.get(y);
-                self.registers.set(x, y);
+                let y = self.machine.registers.get(y);
+                self.machine.registers.set(x, y);
             }
             OpCode::_8XY1 { x, y } => {
-                let val_x = self.registers.get(x);
-                let val_y = self.registers.get(y);
+                let val_x = self.machine.registers.get(x);
+                let val_y = self.machine.registers.get(y);
 
                 let value = val_x | val_y;
-                self.registers.set(x, value);
+                self.machine.registers.set(x, value);
+                if self.settings.quirks.logic {
+                    self.machine.registers.set(Reg::VF, 0);
+                }
             }
             OpCode::_8XY2 { x, y } => {
-                let val_x = self.registers.get(x);
-                let val_y = self.registers.get(y);
+                let val_x = self.machine.registers.get(x);
+                let val_y = self.machine.registers.get(y);
 
                 let value = val_x & val_y;
-                self.registers.set(x, value);
+                self.machine.registers.set(x, value);
+                if self.settings.quirks.logic {
+                    self.machine.registers.set(Reg::VF, 0);
+                }
             }
             OpCode::_8XY3 { x, y } => {
-                let val_x = self.registers.get(x);       
-                let val_y = self.registers.get(y);
-                
+                let val_x = self.machine.registers.get(x);
+                let val_y = self.machine.registers.get(y);
+
                 let value = val_x ^ val_y;
-                self.registers.set(x, value);
+                self.machine.registers.set(x, value);
+                if self.settings.quirks.logic {
+                    self.machine.registers.set(Reg::VF, 0);
+                }
             }
             OpCode::_8XY4 { x, y } => {
-                let val_x = self.registers.get(x);
-                let val_y = self.registers.get(y);
+                let val_x = self.machine.registers.get(x);
+                let val_y = self.machine.registers.get(y);
 
                 let (value, did_overflow) = val_x.overflowing_add(val_y);
 
-                self.registers.set(x, value);
-                self.registers.set(Reg::VF, did_overflow.into());
+                self.machine.registers.set(x, value);
+                self.machine.registers.set(Reg::VF, did_overflow.into());
             }
             OpCode::_8XY5 { x, y } => {
-                let val_x = self.registers.get(x);
-                let val_y = self.registers.get(y);
+                let val_x = self.machine.registers.get(x);
+                let val_y = self.machine.registers.get(y);
                 let (value, did_borrow) = val_x.overflowing_sub(val_y);
 
-                self.registers.set(x, value);
-                self.registers.set(Reg::VF, (!did_borrow).into());
+                self.machine.registers.set(x, value);
+                self.machine.registers.set(Reg::VF, (!did_borrow).into());
             }
-            OpCode::_8XY6 { x, y: _y } => {
-                let val_x = self.registers.get(x);
+            OpCode::_8XY6 { x, y } => {
+                let val_x = self.shift_source(x, y);
 
                 let lsb = 0x1 & val_x;
-                self.registers.set(Reg::VF, lsb);
+                self.machine.registers.set(Reg::VF, lsb);
 
                 let value = val_x >> 1;
-                self.registers.set(x, value);
+                self.machine.registers.set(x, value);
             }
             OpCode::_8XY7 { x, y } => {
-                let val_x = self.registers.get(x);
-                let val_y = self.registers.get(y);
+                let val_x = self.machine.registers.get(x);
+                let val_y = self.machine.registers.get(y);
                 let (value, did_borrow) = val_y.overflowing_sub(val_x);
 
-                self.registers.set(x, value);
-                self.registers.set(Reg::VF, (!did_borrow).into());
+                self.machine.registers.set(x, value);
+                self.machine.registers.set(Reg::VF, (!did_borrow).into());
             }
-            OpCode::_8XYE { x, y: _y } => {
-                let val_x = self.registers.get(x);
+            OpCode::_8XYE { x, y } => {
+                let val_x = self.shift_source(x, y);
 
                 let msb = 0x80 & val_x;
-                self.registers.set(Reg::VF, msb);
+                self.machine.registers.set(Reg::VF, msb);
 
                 let value = val_x << 1;
-                self.registers.set(x, value);
+                self.machine.registers.set(x, value);
             }
             OpCode::_9XY0 { x, y } => {
-                let x = self.registers.get(x);
-                let y = self.registers.get(y);
+                let x = self.machine.registers.get(x);
+                let y = self.machine.registers.get(y);
 
                 if x != y {
-                    self.registers.advance_pc();
+                    self.machine.registers.advance_pc();
                 }
             }
-            OpCode::ANNN(nnn) => self.registers.set_i(nnn),
+            OpCode::ANNN(nnn) => self.machine.registers.set_i(nnn),
             OpCode::BNNN(nnn) => {
-                let v0 = self.registers.get(Reg::V0);
-                let address = nnn + (v0 as u16);
-
-                self.registers.goto(address);
+                // BXNN with the jump quirk
+                let reg = if self.settings.quirks.jump {
+                    Reg::from((nnn >> 8) as usize)
+                } else {
+                    Reg::V0
+                };
+                let address = nnn + (self.machine.registers.get(reg) as u16);
+
+                self.machine.registers.goto(address);
             }
             OpCode::CXNN { reg, value } => {
-                let random_number: u8 = rand::thread_rng().gen();
+                let random_number: u8 = self.rng.gen();
                 let value = random_number & value;
-                self.registers.set(reg, value);
+                self.machine.registers.set(reg, value);
             }
             OpCode::DXYN { x, y, height } => {
-                let x = self.registers.get(x);
-                let y = self.registers.get(y);
-                let i = self.registers.get_i() as usize;
-
-                self.registers.set(Reg::VF, 0);
-
-                for yline in 0..height {
-                    let pixel = self.memory[i + (yline as usize)];
-                    for xline in 0..8 {
-                        let is_on = (pixel & (0x80 >> xline)) != 0;
-                        let y_usize: usize = (y.wrapping_add(yline)) as usize;
-                        let x_usize: usize = (x.wrapping_add(xline)) as usize;
-
-                        let y_idx = y_usize % DISPLAY_HEIGHT;
-                        let x_idx = x_usize % DISPLAY_WIDTH;
-
-                        if is_on {
-                            if self.vram[y_idx][x_idx] {
-                                self.registers.set(Reg::VF, 1);
-                            }
-                            self.vram[y_idx][x_idx] ^= true;
-                        }
-                    }
-                }
-
-                self.draw_flag = true;
+                let x = self.machine.registers.get(x) as usize;
+                let y = self.machine.registers.get(y) as usize;
+                let i = self.machine.registers.get_i() as usize;
+                self.strict_read(i, height as usize)?;
+                Self::check_range(i, height as usize)?;
+
+                let sprite = &self.machine.memory[i..i + (height as usize)];
+                let collision = if self.settings.quirks.wrap {
+                    self.machine.vram.draw_sprite(x, y, sprite)
+                } else {
+                    self.machine.vram.draw_sprite_clipped(x, y, sprite)
+                };
+                self.machine.registers.set(Reg::VF, collision.into());
+                self.observe_reads(i, height as usize);
+                self.notify(|observer, _| {
+                    observer.draw(i as u16, x as u8, y as u8, height, collision)
+                });
+
+                self.waiting_for_vblank = self.settings.quirks.vblank;
             }
             OpCode::EX9E(reg) => {
-                let expected_key = self.registers.get(reg);
-                if self.platform.key_is_pressed(&expected_key) {
-                    self.registers.advance_pc();
+                let expected_key = self.machine.registers.get(reg);
+                if self.key_is_pressed(expected_key) {
+                    self.machine.registers.advance_pc();
                 }
             }
             OpCode::EXA1(reg) => {
-                let expected_key = self.registers.get(reg);
-                if !self.platform.key_is_pressed(&expected_key) {
-                    self.registers.advance_pc();
+                let expected_key = self.machine.registers.get(reg);
+                if !self.key_is_pressed(expected_key) {
+                    self.machine.registers.advance_pc();
                 }
             }
             OpCode::FX07(reg) => {
-                let value = self.registers.get(Reg::DelayTimer);
-                self.registers.set(reg, value);
+                let value = self.machine.registers.get(Reg::DelayTimer);
+                self.machine.registers.set(reg, value);
             }
             OpCode::FX0A(dest_reg) => {
-                let val = self.platform.await_keypress()?;
-                self.registers.set(dest_reg, val);
+                let awaited_key = self.awaited_key;
+                match awaited_key {
+                    // like the VIP, the key counts once it is let go
+                    Some(key) if !self.key_is_pressed(key) => {
+                        self.awaited_key = None;
+                        self.machine.registers.set(dest_reg, key);
+                    }
+                    _ => {
+                        if awaited_key.is_none() {
+                            self.awaited_key = (0..16).find(|key| self.key_is_pressed(*key));
+                        }
+
+                        // run FX0A again next cycle, timers and the screen carry on
+                        self.machine.registers.goto(self.machine.registers.pc() - 2);
+                    }
+                }
             }
             OpCode::FX15(reg) => {
-                let value = self.registers.get(reg);
-                self.registers.set(Reg::DelayTimer, value);
+                let value = self.machine.registers.get(reg);
+                self.machine.registers.set(Reg::DelayTimer, value);
+                self.notify(|observer, _| observer.timer_write(Reg::DelayTimer, value));
             }
             OpCode::FX18(reg) => {
-                let value = self.registers.get(reg);
-                self.registers.set(Reg::SoundTimer, value)
+                let value = self.machine.registers.get(reg);
+                self.machine.registers.set(Reg::SoundTimer, value);
+                self.notify(|observer, _| observer.timer_write(Reg::SoundTimer, value));
             }
             OpCode::FX1E(reg) => {
-                let i = self.registers.get_i();
-                let val = self.registers.get(reg) as u16;
-                self.registers.set_i(i + val);
+                let i = self.machine.registers.get_i();
+                let val = self.machine.registers.get(reg) as u16;
+                self.machine.registers.set_i(i.wrapping_add(val));
+                self.strict_index()?;
             }
             OpCode::FX29(reg) => {
-                let character = self.registers.get(reg) as usize;
+                let character = self.machine.registers.get(reg);
 
                 // 5 rows per character
-                let offset: usize = character * 0x5;
+                let offset: usize = character as usize * 0x5;
 
                 let sprite_addr = FONT_SET_START_ADDR + offset;
 
-                // sanity check
                 if sprite_addr >= FONT_SET_END_ADDR {
-                    panic!("character was greater than 0xf");
+                    return Err(EmulatorError::InvalidCharacter(character));
                 }
 
-                self.registers.set_i(sprite_addr as u16);
+                self.machine.registers.set_i(sprite_addr as u16);
             }
             OpCode::FX33(reg) => {
-                let val = self.registers.get(reg);
+                let val = self.machine.registers.get(reg);
                 let bcd = bcd(val);
-                let i = self.registers.get_i() as usize;
+                let i = self.machine.registers.get_i() as usize;
+                self.strict_write(i, 3)?;
+                Self::check_range(i, 3)?;
 
-                self.memory[i] = bcd[0];
-                self.memory[i + 1] = bcd[1];
-                self.memory[i + 2] = bcd[2];
+                self.write_memory(i, bcd[0]);
+                self.write_memory(i + 1, bcd[1]);
+                self.write_memory(i + 2, bcd[2]);
             }
             OpCode::FX55(reg) => {
                 // store v0 to vreg (inclusive) into memory
-                let i = self.registers.get_i() as usize;
+                let i = self.machine.registers.get_i() as usize;
 
                 let start = 0x0;
                 let end: usize = reg.into();
-
-                let new_i = i + end + 1;
+                self.strict_write(i, end + 1)?;
+                Self::check_range(i, end + 1)?;
 
                 for idx in start..=end {
                     let reg: Reg = (start + idx).into();
-                    let val = self.registers.get(reg);
-                    self.memory[i + idx] = val;
+                    let val = self.machine.registers.get(reg);
+                    self.write_memory(i + idx, val);
                 }
 
-                // On the original interpreter,
-                // when the operation is done, I = I + X + 1
-                self.registers.set_i(new_i as u16);
+                self.advance_i_after_memory_op(end);
+                self.strict_index()?;
             }
             OpCode::FX65(reg) => {
                 // fill v0 to vreg (inclusive) with values from memory
-                let i = self.registers.get_i() as usize;
+                let i = self.machine.registers.get_i() as usize;
 
                 let start = 0x0;
                 let end: usize = reg.into();
-
-                let new_i = i + end + 1;
+                self.strict_read(i, end + 1)?;
+                Self::check_range(i, end + 1)?;
 
                 for idx in start..=end {
                     let reg: Reg = (start + idx).into();
-                    let val = self.memory[i + idx];
-                    self.registers.set(reg, val);
+                    let val = self.machine.memory[i + idx];
+                    self.machine.registers.set(reg, val);
                 }
+                self.observe_reads(i, end + 1);
 
-                // On the original interpreter,
-                // when the operation is done, I = I + X + 1
-                self.registers.set_i(new_i as u16);
+                self.advance_i_after_memory_op(end);
+                self.strict_index()?;
             }
         }
 
         Ok(())
     }
+
+    /// That the `len` bytes from `start` are all in memory, before touching them
+    fn check_range(start: usize, len: usize) -> Result<(), EmulatorError> {
+        if start + len > MEMORY_SIZE {
+            return Err(EmulatorError::InvalidAddress(start.max(MEMORY_SIZE) as u16));
+        }
+
+        Ok(())
+    }
+
+    /// Where the return address of the call `depth` deep goes, with the stack in
+    /// memory
+    fn stack_slot(depth: usize) -> usize {
+        MEMORY_STACK_END - 2 * (depth + 1)
+    }
+
+    fn push_return(&mut self, ret_address: u16) -> Result<(), EmulatorError> {
+        let depth = self.machine.stack.len();
+        if depth >= self.settings.stack.depth {
+            return Err(EmulatorError::StackOverflow(ret_address - 2));
+        }
+
+        if self.settings.stack.in_memory {
+            let slot = Self::stack_slot(depth);
+            let [high, low] = ret_address.to_be_bytes();
+            self.write_memory(slot, high);
+            self.write_memory(slot + 1, low);
+        }
+        self.machine.stack.push(ret_address);
+
+        Ok(())
+    }
+
+    fn pop_return(&mut self) -> Result<u16, EmulatorError> {
+        let Some(ret_address) = self.machine.stack.pop() else {
+            return Err(EmulatorError::StackUnderflow(
+                self.machine.registers.pc() - 2,
+            ));
+        };
+
+        if !self.settings.stack.in_memory {
+            return Ok(ret_address);
+        }
+        // whatever the rom left there, like the VIP would
+        let slot = Self::stack_slot(self.machine.stack.len());
+        self.observe_reads(slot, 2);
+        Ok(u16::from_be_bytes([
+            self.machine.memory[slot],
+            self.machine.memory[slot + 1],
+        ]))
+    }
+
+    /// The value 8XY6 and 8XYE shift
+    fn shift_source(&self, x: Reg, y: Reg) -> u8 {
+        if self.settings.quirks.shift {
+            self.machine.registers.get(x)
+        } else {
+            self.machine.registers.get(y)
+        }
+    }
+
+    fn advance_i_after_memory_op(&mut self, x: usize) {
+        let quirks = self.settings.quirks;
+        if quirks.memory_leave_i_unchanged {
+            return;
+        }
+
+        // On the original interpreter,
+        // when the operation is done, I = I + X + 1
+        let increment = if quirks.memory_increment_by_x {
+            x
+        } else {
+            x + 1
+        };
+        let i = self.machine.registers.get_i();
+        self.machine
+            .registers
+            .set_i(i.wrapping_add(increment as u16));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
+
+    use super::Emulator;
+    use crate::platform::{Audio, Display, HostCommand, HostControl, Input, Peripherals};
+    use crate::registers::Reg;
+    use crate::{
+        EmulatorError, Headless, Machine, Observer, Quirks, RecordingStart, Settings, Stack,
+        Variant, Vram,
+    };
+
+    /// Counts presented frames
+    struct FrameCounter {
+        frames: Rc<Cell<usize>>,
+    }
+
+    impl Display for FrameCounter {
+        fn draw(&mut self, _vram: &Vram) {
+            self.frames.set(self.frames.get() + 1);
+        }
+    }
+
+    /// Records the frame the beep started and stopped on
+    struct SpyAudio {
+        frames: Rc<Cell<usize>>,
+        started: Rc<Cell<Option<usize>>>,
+        stopped: Rc<Cell<Option<usize>>>,
+    }
+
+    impl Audio for SpyAudio {
+        fn start_beep(&mut self) {
+            self.started.set(Some(self.frames.get()));
+        }
+
+        fn stop_beep(&mut self) {
+            self.stopped.set(Some(self.frames.get()));
+        }
+    }
+
+    struct NoInput;
+
+    impl Input for NoInput {
+        fn scan_keys(&mut self) {}
+
+        fn key_is_pressed(&self, _key: &u8) -> bool {
+            false
+        }
+
+        fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
+            Err(EmulatorError::Exit)
+        }
+    }
+
+    /// Quits after a fixed amount of frames
+    struct QuitAfter(usize);
+
+    impl HostControl for QuitAfter {
+        fn should_quit(&mut self) -> bool {
+            self.0 = self.0.saturating_sub(1);
+            self.0 == 0
+        }
+
+        fn throttle(&self) -> bool {
+            false
+        }
+    }
+
+    /// Sends commands on the given frames, then quits
+    struct Scripted {
+        frame: usize,
+        commands: Vec<(usize, HostCommand)>,
+        quit_at: usize,
+    }
+
+    impl HostControl for Scripted {
+        fn should_quit(&mut self) -> bool {
+            self.frame += 1;
+            self.frame == self.quit_at
+        }
+
+        fn next_command(&mut self) -> Option<HostCommand> {
+            let next = self
+                .commands
+                .iter()
+                .position(|(frame, _)| *frame == self.frame)?;
+            Some(self.commands.remove(next).1)
+        }
+
+        fn throttle(&self) -> bool {
+            false
+        }
+    }
+
+    fn scripted(rom: Vec<u8>, commands: Vec<(usize, HostCommand)>, quit_at: usize) -> Emulator {
+        let peripherals = Peripherals::new(
+            Box::new(Headless::new(None)),
+            Box::new(Headless::new(None)),
+            Box::new(Headless::new(None)),
+            Box::new(Scripted {
+                frame: 0,
+                commands,
+                quit_at,
+            }),
+        );
+
+        Emulator::new(rom, peripherals, Settings::default(), false).unwrap()
+    }
+
+    fn run_scripted(rom: Vec<u8>, commands: Vec<(usize, HostCommand)>, quit_at: usize) -> Emulator {
+        let mut emulator = scripted(rom, commands, quit_at);
+        emulator.start();
+        emulator
+    }
+
+    /// The error `rom` stops on within a few frames, if any
+    fn stops_on(rom: &[u8]) -> Option<String> {
+        let mut emulator = scripted(rom.to_vec(), Vec::new(), 10);
+        let error = Rc::new(RefCell::new(None));
+        let seen = Rc::clone(&error);
+        emulator.set_crash_handler(Box::new(move |crash| {
+            *seen.borrow_mut() = Some(format!("{:?}", crash.error))
+        }));
+
+        emulator.start();
+        error.take()
+    }
+
+    #[test]
+    fn font_characters_past_f_stop_the_rom() {
+        // V0 = 0x20, I = its character
+        assert_eq!(
+            stops_on(&[0x60, 0x20, 0xF0, 0x29]).as_deref(),
+            Some("InvalidCharacter(32)")
+        );
+    }
+
+    #[test]
+    fn loading_registers_past_the_end_of_memory_stops_the_rom() {
+        // I = FFF, load V0..=V3
+        assert_eq!(
+            stops_on(&[0xAF, 0xFF, 0xF3, 0x65]).as_deref(),
+            Some("InvalidAddress(4096)")
+        );
+    }
+
+    #[test]
+    fn storing_bcd_past_the_end_of_memory_stops_the_rom() {
+        // I = FFF, store V0 as BCD
+        assert_eq!(
+            stops_on(&[0xAF, 0xFF, 0xF0, 0x33]).as_deref(),
+            Some("InvalidAddress(4096)")
+        );
+    }
+
+    #[test]
+    fn drawing_past_the_end_of_memory_stops_the_rom() {
+        // I = FFF, draw 15 rows from there
+        assert_eq!(
+            stops_on(&[0xAF, 0xFF, 0xD0, 0x0F]).as_deref(),
+            Some("InvalidAddress(4096)")
+        );
+    }
+
+    #[test]
+    fn running_the_last_byte_of_memory_stops_the_rom() {
+        // jump to FFF, which has no room for an instruction
+        assert_eq!(
+            stops_on(&[0x1F, 0xFF]).as_deref(),
+            Some("InvalidAddress(4096)")
+        );
+    }
+
+    #[test]
+    fn jumping_past_the_end_of_memory_stops_the_rom() {
+        // V0 = FF, jump to FFF + V0
+        assert_eq!(
+            stops_on(&[0x60, 0xFF, 0xBF, 0xFF]).as_deref(),
+            Some("InvalidAddress(4350)")
+        );
+    }
+
+    #[test]
+    fn pausing_stops_the_timers_until_a_frame_advance() {
+        // V0 = 5, delay timer = V0, then spin forever
+        let rom = vec![0x60, 0x05, 0xF0, 0x15, 0x12, 0x04];
+
+        let paused = run_scripted(rom.clone(), vec![(1, HostCommand::TogglePause)], 8);
+        assert_eq!(paused.machine.registers.get(Reg::DelayTimer), 4);
+
+        let advanced = run_scripted(
+            rom,
+            vec![
+                (1, HostCommand::TogglePause),
+                (3, HostCommand::FrameAdvance),
+                (5, HostCommand::FrameAdvance),
+            ],
+            8,
+        );
+        assert_eq!(advanced.machine.registers.get(Reg::DelayTimer), 2);
+    }
+
+    #[test]
+    fn breakpoints_pause_before_the_instruction() {
+        // V1 += 1 forever
+        let rom = vec![0x71, 0x01, 0x12, 0x00];
+
+        let emulator = run_scripted(
+            rom,
+            vec![
+                (0, HostCommand::ToggleBreakpoint(0x202)),
+                // make it V1 += 5, and run on to the breakpoint again
+                (
+                    2,
+                    HostCommand::Poke {
+                        address: 0x201,
+                        value: 5,
+                    },
+                ),
+                (2, HostCommand::TogglePause),
+            ],
+            4,
+        );
+        assert!(emulator.paused);
+        assert_eq!(emulator.machine.registers.pc(), 0x202);
+        assert_eq!(emulator.machine.registers.get(Reg::V1), 6);
+    }
+
+    #[test]
+    fn breakpoints_at_the_first_instruction_pause_before_it() {
+        // V1 += 1 forever
+        let rom = vec![0x71, 0x01, 0x12, 0x00];
+
+        let paused = run_scripted(
+            rom.clone(),
+            vec![(0, HostCommand::ToggleBreakpoint(0x200))],
+            3,
+        );
+        assert_eq!(paused.machine.registers.pc(), 0x200);
+        assert_eq!(paused.machine.registers.get(Reg::V1), 0);
+
+        // carrying on runs it, and around to the breakpoint again
+        let resumed = run_scripted(
+            rom,
+            vec![
+                (0, HostCommand::ToggleBreakpoint(0x200)),
+                (2, HostCommand::TogglePause),
+            ],
+            4,
+        );
+        assert!(resumed.paused);
+        assert_eq!(resumed.machine.registers.pc(), 0x200);
+        assert_eq!(resumed.machine.registers.get(Reg::V1), 1);
+    }
+
+    #[test]
+    fn loading_a_rom_drops_the_cheats_and_breakpoints() {
+        // V1 += 1 forever
+        let rom = vec![0x71, 0x01, 0x12, 0x00];
+
+        let emulator = run_scripted(
+            rom.clone(),
+            vec![
+                (0, HostCommand::ToggleBreakpoint(0x202)),
+                (0, HostCommand::SetCheats(vec!["V1=09".parse().unwrap()])),
+                (1, HostCommand::TogglePause),
+                (
+                    1,
+                    HostCommand::LoadRom {
+                        rom,
+                        settings: Settings::default(),
+                    },
+                ),
+            ],
+            3,
+        );
+
+        assert!(emulator.cheats.is_empty());
+        assert!(emulator.breakpoints.is_empty());
+        // two frames of 5 additions, without V1 held at 9 or a pause at 202
+        assert_eq!(emulator.machine.registers.get(Reg::V1), 10);
+    }
+
+    #[test]
+    fn resets_start_the_recording_over() {
+        // V0 = random, spin
+        let rom = vec![0xC0, 0xFF, 0x12, 0x02];
+
+        let mut emulator = run_scripted(
+            rom,
+            vec![
+                (3, HostCommand::Reset),
+                (
+                    4,
+                    HostCommand::Poke {
+                        address: 0x300,
+                        value: 7,
+                    },
+                ),
+            ],
+            6,
+        );
+
+        assert_eq!(emulator.recording_start, RecordingStart::Reset);
+        assert_eq!((emulator.frames, emulator.inputs.len()), (3, 3));
+        assert_eq!(emulator.pokes, [(1, 0x300, 7)]);
+        // only the number drawn since the reset came from the seed
+        let mut rng = StdRng::seed_from_u64(emulator.seed);
+        assert_eq!(emulator.machine.registers.get(Reg::V0), rng.gen::<u8>());
+        assert_eq!(emulator.rng.gen::<u64>(), rng.gen::<u64>());
+    }
+
+    #[test]
+    fn loading_a_state_restores_the_machine() {
+        // V1 += 1 forever, 5 times a frame
+        let rom = vec![0x71, 0x01, 0x12, 0x00];
+
+        let emulator = run_scripted(
+            rom,
+            vec![
+                (2, HostCommand::SaveState(3)),
+                (5, HostCommand::LoadState(3)),
+                (5, HostCommand::TogglePause),
+            ],
+            7,
+        );
+
+        assert_eq!(emulator.machine.registers.get(Reg::V1), 10);
+    }
+
+    fn headless(rom: Vec<u8>) -> Emulator {
+        headless_with(rom, Settings::default(), None)
+    }
+
+    fn headless_with(rom: Vec<u8>, settings: Settings, frames: Option<u64>) -> Emulator {
+        Emulator::new(
+            rom,
+            Peripherals::from_platform(Headless::new(frames)),
+            settings,
+            false,
+        )
+        .unwrap()
+    }
+
+    fn with_quirks(rom: Vec<u8>, quirks: Quirks) -> Emulator {
+        let settings = Settings {
+            quirks,
+            ..Settings::default()
+        };
+        headless_with(rom, settings, None)
+    }
+
+    #[test]
+    fn shift_quirk_picks_the_register_shifted() {
+        // V1 = 6, V0 = V1 >> 1
+        let rom = vec![0x61, 0x06, 0x80, 0x16];
+
+        let mut in_place = with_quirks(rom.clone(), Quirks::default());
+        let mut from_vy = with_quirks(
+            rom,
+            Quirks {
+                shift: false,
+                ..Quirks::default()
+            },
+        );
+        for _ in 0..2 {
+            in_place.cycle().unwrap();
+            from_vy.cycle().unwrap();
+        }
+
+        assert_eq!(in_place.machine.registers.get(Reg::V0), 0);
+        assert_eq!(from_vy.machine.registers.get(Reg::V0), 3);
+    }
+
+    #[test]
+    fn memory_quirks_change_how_far_i_moves() {
+        // I = 0x300, store V0..=V2
+        let rom = vec![0xA3, 0x00, 0xF2, 0x55];
+        let i_after = |quirks| {
+            let mut emulator = with_quirks(rom.clone(), quirks);
+            emulator.cycle().unwrap();
+            emulator.cycle().unwrap();
+            emulator.machine.registers.get_i()
+        };
+
+        assert_eq!(i_after(Quirks::default()), 0x303);
+        let by_x = Quirks {
+            memory_increment_by_x: true,
+            ..Quirks::default()
+        };
+        assert_eq!(i_after(by_x), 0x302);
+        let unchanged = Quirks {
+            memory_leave_i_unchanged: true,
+            ..Quirks::default()
+        };
+        assert_eq!(i_after(unchanged), 0x300);
+    }
+
+    #[test]
+    fn jump_and_logic_quirks() {
+        // VF = 1, V0 |= V1, jump to 0x300 + V3 (or V0)
+        let rom = vec![0x6F, 0x01, 0x63, 0x04, 0x80, 0x11, 0xB3, 0x00];
+        let quirks = Quirks {
+            jump: true,
+            logic: true,
+            ..Quirks::default()
+        };
+        let mut emulator = with_quirks(rom, quirks);
+        for _ in 0..4 {
+            emulator.cycle().unwrap();
+        }
+
+        assert_eq!(emulator.machine.registers.get(Reg::VF), 0);
+        assert_eq!(emulator.machine.registers.pc(), 0x304);
+    }
+
+    #[test]
+    fn vblank_quirk_draws_once_a_frame() {
+        // V1 += 1, draw, loop
+        let rom = vec![0x71, 0x01, 0xD0, 0x01, 0x12, 0x00];
+        let settings = Settings {
+            quirks: Quirks {
+                vblank: true,
+                ..Quirks::default()
+            },
+            ..Settings::default()
+        };
+
+        let mut emulator = headless_with(rom, settings, Some(4));
+        emulator.start();
+
+        assert_eq!(emulator.machine.registers.get(Reg::V1), 4);
+    }
+
+    #[test]
+    fn reset_keeps_memory_outside_the_rom() {
+        let mut emulator = headless(vec![0x12, 0x00]);
+        emulator.machine.registers.set(Reg::V3, 7);
+        emulator.machine.registers.goto(0x204);
+        emulator.machine.memory[0x200] = 0xFF;
+        emulator.machine.memory[0x300] = 0xAB;
+
+        emulator.reset();
+        assert_eq!(emulator.machine.registers.get(Reg::V3), 0);
+        assert_eq!(emulator.machine.registers.pc(), 0x200);
+        assert_eq!(emulator.machine.memory[0x200], 0x12);
+        assert_eq!(emulator.machine.memory[0x300], 0xAB);
+
+        emulator.hard_reset();
+        assert_eq!(emulator.machine.memory[0x200], 0x12);
+        assert_eq!(emulator.machine.memory[0x300], 0);
+    }
+
+    #[test]
+    fn load_rom_swaps_the_rom() {
+        let mut emulator = headless(vec![0x12, 0x00, 0x12, 0x02]);
+
+        emulator.load_rom(vec![0x00, 0xE0]).unwrap();
+        assert_eq!(
+            emulator.machine.memory[0x200..0x204],
+            [0x00, 0xE0, 0x00, 0x00]
+        );
+        assert_eq!(emulator.machine.memory[0x050], 0xF0);
+
+        assert!(emulator.load_rom(vec![0; 4096]).is_err());
+    }
+
+    /// Holds `key` down on the frames in `held`
+    struct PressKey {
+        frame: usize,
+        key: u8,
+        held: std::ops::Range<usize>,
+    }
+
+    impl Input for PressKey {
+        fn scan_keys(&mut self) {
+            self.frame += 1;
+        }
+
+        fn key_is_pressed(&self, key: &u8) -> bool {
+            *key == self.key && self.held.contains(&self.frame)
+        }
+
+        fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
+            Err(EmulatorError::Exit)
+        }
+    }
+
+    fn wait_for_key(quit_at: usize) -> Emulator {
+        // V1 = 30, delay timer = V1, V0 = key, spin
+        let rom = vec![0x61, 0x1E, 0xF1, 0x15, 0xF0, 0x0A, 0x12, 0x06];
+        let peripherals = Peripherals::new(
+            Box::new(Headless::new(None)),
+            Box::new(Headless::new(None)),
+            Box::new(PressKey {
+                frame: 0,
+                key: 0x5,
+                held: 3..5,
+            }),
+            Box::new(QuitAfter(quit_at)),
+        );
+
+        let mut emulator = Emulator::new(rom, peripherals, Settings::default(), false).unwrap();
+        emulator.start();
+        emulator
+    }
+
+    #[test]
+    fn fx0a_completes_when_the_key_is_released() {
+        let held = wait_for_key(4);
+        assert_eq!(held.machine.registers.pc(), 0x204);
+        assert_eq!(held.machine.registers.get(Reg::V0), 0);
+        // the timers kept running while waiting
+        assert_eq!(held.machine.registers.get(Reg::DelayTimer), 30 - 3);
+
+        let released = wait_for_key(6);
+        assert_eq!(released.machine.registers.pc(), 0x206);
+        assert_eq!(released.machine.registers.get(Reg::V0), 0x5);
+    }
+
+    #[test]
+    fn beeps_for_as_long_as_the_sound_timer_is_set() {
+        // V0 = 5, sound timer = V0, then spin forever
+        let rom = vec![0x60, 0x05, 0xF0, 0x18, 0x12, 0x04];
+        let frames = Rc::new(Cell::new(0));
+        let started = Rc::new(Cell::new(None));
+        let stopped = Rc::new(Cell::new(None));
+
+        let peripherals = Peripherals::new(
+            Box::new(FrameCounter {
+                frames: Rc::clone(&frames),
+            }),
+            Box::new(SpyAudio {
+                frames: Rc::clone(&frames),
+                started: Rc::clone(&started),
+                stopped: Rc::clone(&stopped),
+            }),
+            Box::new(NoInput),
+            Box::new(QuitAfter(16)),
+        );
+
+        Emulator::new(rom, peripherals, Settings::default(), false)
+            .unwrap()
+            .start();
+
+        let (started, stopped) = (started.get().unwrap(), stopped.get().unwrap());
+        assert_eq!(started, 1);
+        assert_eq!(stopped - started, 5);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        instructions: Vec<u16>,
+        events: Vec<String>,
+    }
+
+    impl Observer for Recorder {
+        fn before_instruction(&mut self, _machine: &Machine, address: u16, _instruction: u16) {
+            self.instructions.push(address);
+        }
+
+        fn memory_read(&mut self, address: u16, value: u8) {
+            self.events.push(format!("read {:03X} {}", address, value));
+        }
+
+        fn memory_write(&mut self, address: u16, old: u8, new: u8) {
+            self.events
+                .push(format!("write {:03X} {} -> {}", address, old, new));
+        }
+
+        fn draw(&mut self, sprite: u16, x: u8, y: u8, height: u8, collision: bool) {
+            self.events.push(format!(
+                "draw {:03X} at {},{} x{} {}",
+                sprite, x, y, height, collision
+            ));
+        }
+
+        fn call(&mut self, from: u16, to: u16) {
+            self.events.push(format!("call {:03X} -> {:03X}", from, to));
+        }
+
+        fn ret(&mut self, from: u16, to: u16) {
+            self.events.push(format!("ret {:03X} -> {:03X}", from, to));
+        }
+
+        fn timer_write(&mut self, timer: Reg, value: u8) {
+            self.events.push(format!("{:?} = {}", timer, value));
+        }
+    }
+
+    #[test]
+    fn observers_see_what_the_rom_does() {
+        let rom = vec![
+            0x22, 0x06, // call 0x206
+            0xF0, 0x15, // delay timer = V0
+            0x12, 0x04, // spin
+            0x60, 0x7B, // V0 = 123
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x33, // BCD of V0 at I
+            0xD1, 0x13, // draw the 3 digits at V1, V1
+            0x00, 0xEE, // return
+        ];
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let mut emulator = headless(rom);
+        emulator.add_observer(Box::new(Rc::clone(&recorder)));
+
+        for _ in 0..8 {
+            emulator.cycle().unwrap();
+        }
+
+        let recorder = recorder.borrow();
+        assert_eq!(
+            recorder.instructions,
+            [0x200, 0x206, 0x208, 0x20A, 0x20C, 0x20E, 0x202, 0x204]
+        );
+        assert_eq!(
+            recorder.events,
+            [
+                "call 200 -> 206",
+                "write 300 0 -> 1",
+                "write 301 0 -> 2",
+                "write 302 0 -> 3",
+                "read 300 1",
+                "read 301 2",
+                "read 302 3",
+                "draw 300 at 0,0 x3 false",
+                "ret 20E -> 202",
+                "DelayTimer = 123",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_stack_has_a_depth_and_can_live_in_memory() {
+        let vip = Settings {
+            stack: Stack::for_variant(Variant::Chip8),
+            ..Settings::default()
+        };
+
+        // call itself forever
+        let mut emulator = headless_with(vec![0x22, 0x00], vip, None);
+        for _ in 0..12 {
+            emulator.cycle().unwrap();
+        }
+        assert!(matches!(
+            emulator.cycle(),
+            Err(EmulatorError::StackOverflow(0x200))
+        ));
+
+        let mut emulator = headless(vec![0x00, 0xEE]);
+        assert!(matches!(
+            emulator.cycle(),
+            Err(EmulatorError::StackUnderflow(0x200))
+        ));
+
+        let rom = vec![
+            0x22, 0x04, // call 0x204
+            0x12, 0x02, // spin, where the call would return
+            0x60, 0x02, // V0 = 0x02
+            0x61, 0x0A, // V1 = 0x0A
+            0xAE, 0xCE, // I = 0xECE, where the VIP keeps the first return address
+            0xF1, 0x55, // write 0x020A over it
+            0x00, 0xEE, // return
+        ];
+        let mut emulator = headless_with(rom.clone(), vip, None);
+        emulator.cycle().unwrap();
+        assert_eq!(emulator.machine.memory[0xECE..0xED0], [0x02, 0x02]);
+        for _ in 0..5 {
+            emulator.cycle().unwrap();
+        }
+        assert_eq!(emulator.machine.registers.pc(), 0x20A);
+
+        // SCHIP keeps it out of reach
+        let mut emulator = headless(rom);
+        for _ in 0..6 {
+            emulator.cycle().unwrap();
+        }
+        assert_eq!(emulator.machine.registers.pc(), 0x202);
+    }
+
+    #[test]
+    fn crash_handlers_get_what_led_up_to_the_error() {
+        // I = 0x100, store V0 there, which strict mode stops at
+        let mut emulator =
+            headless_with(vec![0xA1, 0x00, 0xF0, 0x55], Settings::default(), Some(5));
+        emulator.set_strict(true);
+        let crashes = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&crashes);
+        emulator.set_crash_handler(Box::new(move |crash| {
+            seen.borrow_mut()
+                .push((crash.address, crash.trace.clone(), crash.inputs.len()))
+        }));
+
+        emulator.start();
+
+        assert_eq!(
+            *crashes.borrow(),
+            [(0x202, vec![(0x200, 0xA100), (0x202, 0xF055)], 1)]
+        );
+    }
+
+    #[test]
+    fn errors_stop_the_rom_like_a_quit() {
+        // return with nothing on the stack, without a frame limit to stop at
+        let mut emulator = headless_with(vec![0x00, 0xEE], Settings::default(), None);
+        let crashes = Rc::new(Cell::new(0));
+        let seen = Rc::clone(&crashes);
+        emulator.set_crash_handler(Box::new(move |_| seen.set(seen.get() + 1)));
+
+        emulator.start();
+
+        assert_eq!(crashes.get(), 1);
+    }
+
+    #[test]
+    fn quitting_in_debug_mode_is_not_a_crash() {
+        // spin, with the headless keypad quitting when debug mode waits on it
+        let mut emulator = Emulator::new(
+            vec![0x12, 0x00],
+            Peripherals::from_platform(Headless::new(None)),
+            Settings::default(),
+            true,
+        )
+        .unwrap();
+        let crashes = Rc::new(Cell::new(0));
+        let seen = Rc::clone(&crashes);
+        emulator.set_crash_handler(Box::new(move |_| seen.set(seen.get() + 1)));
+
+        emulator.start();
+
+        assert_eq!(crashes.get(), 0);
+    }
+
+    #[test]
+    fn strict_mode_stops_at_suspicious_instructions() {
+        let strict_error = |rom: Vec<u8>, cycles: usize| {
+            let mut emulator = headless(rom);
+            emulator.set_strict(true);
+            for _ in 0..cycles - 1 {
+                emulator.cycle().unwrap();
+            }
+            match emulator.cycle() {
+                Err(EmulatorError::Strict(problem)) => problem,
+                other => panic!("expected a strict error, got {:?}", other),
+            }
+        };
+
+        // I = 0x100, store V0 there
+        assert_eq!(
+            strict_error(vec![0xA1, 0x00, 0xF0, 0x55], 2),
+            "202: F055: writing 100, inside the interpreter below 200"
+        );
+        // I = 0x400, draw what was never written there
+        assert_eq!(
+            strict_error(vec![0xA4, 0x00, 0xD0, 0x01], 2),
+            "202: D001: reading 400, which was never written"
+        );
+        // jump past the end of the rom
+        assert_eq!(
+            strict_error(vec![0x12, 0x02], 2),
+            "202: 0000: running 202, which was never written"
+        );
+        // I = 0xFFF, I += 1
+        assert_eq!(
+            strict_error(vec![0xAF, 0xFF, 0x60, 0x01, 0xF0, 0x1E], 3),
+            "204: F01E: I moved past FFF to 1000"
+        );
+        // without strict mode the same rom keeps going
+        let mut emulator = headless(vec![0xA1, 0x00, 0xF0, 0x55, 0x12, 0x00]);
+        for _ in 0..3 {
+            emulator.cycle().unwrap();
+        }
+    }
 }
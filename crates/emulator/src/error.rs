@@ -2,4 +2,16 @@
 pub enum EmulatorError {
     Exit,
     UnknownOpCode(String),
+    /// The rom doesn't fit in memory, with its size in bytes
+    RomTooLarge(usize),
+    /// An address outside of memory
+    InvalidAddress(u16),
+    /// FX29 with a value past F, which has no font character
+    InvalidCharacter(u8),
+    /// A call with the stack already full, at this address
+    StackOverflow(u16),
+    /// A return with nothing on the stack, at this address
+    StackUnderflow(u16),
+    /// Something strict mode doesn't allow, with the instruction that did it
+    Strict(String),
 }
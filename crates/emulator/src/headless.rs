@@ -0,0 +1,54 @@
+use crate::platform::{Audio, Display, HostControl, Input};
+use crate::{EmulatorError, Vram};
+
+/// A platform without any window, sound or keyboard. It runs as fast as possible,
+/// for a fixed amount of frames if given one.
+pub struct Headless {
+    frames_left: Option<u64>,
+}
+
+impl Headless {
+    pub fn new(frames: Option<u64>) -> Self {
+        Self {
+            frames_left: frames,
+        }
+    }
+}
+
+impl Display for Headless {
+    fn draw(&mut self, _vram: &Vram) {}
+}
+
+impl Audio for Headless {
+    fn start_beep(&mut self) {}
+    fn stop_beep(&mut self) {}
+}
+
+impl Input for Headless {
+    fn scan_keys(&mut self) {}
+
+    fn key_is_pressed(&self, _key: &u8) -> bool {
+        false
+    }
+
+    fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
+        // nobody is ever going to press a key
+        Err(EmulatorError::Exit)
+    }
+}
+
+impl HostControl for Headless {
+    fn should_quit(&mut self) -> bool {
+        match self.frames_left.as_mut() {
+            Some(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+            None => false,
+        }
+    }
+
+    fn throttle(&self) -> bool {
+        false
+    }
+}
@@ -1,22 +1,60 @@
+mod cheats;
+mod coverage;
+mod crash;
+mod detect;
 mod emulator;
 mod error;
+mod headless;
+mod machine;
+mod observer;
 mod opcode;
 mod platform;
+mod quirks;
 mod registers;
+mod render;
+mod rom;
+mod sound;
+mod strict;
 mod utils;
+mod vram;
+mod wav;
 
-pub const DISPLAY_HEIGHT: usize = 32;
-pub const DISPLAY_WIDTH: usize = 64;
-// TODO bool should be replaced with u8's and bitwise ops
-pub type Vram = [[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+pub const DISPLAY_HEIGHT: usize = Vram::HEIGHT;
+pub const DISPLAY_WIDTH: usize = Vram::WIDTH;
 
+pub use cheats::{Cheat, Filter, Search, Target};
+pub use coverage::{Access, Coverage};
+pub use crash::{Crash, CrashHandler, RecordingStart};
+pub use detect::{detect_quirks, QuirkGuess, QuirkReport};
+pub use emulator::{Emulator, MAX_ROM_SIZE, STATE_SLOTS};
 pub use error::EmulatorError;
+pub use headless::Headless;
+pub use machine::{Frame, Machine, MEMORY_SIZE};
+pub use observer::Observer;
+pub use opcode::mnemonic;
 use opcode::OpCode;
-pub use platform::Platform;
+pub use platform::{Audio, Display, HostCommand, HostControl, Input, Peripherals, Platform};
+pub use quirks::{Quirk, Quirks, Settings, Stack, DEFAULT_TICKRATE};
+pub use registers::{Reg, Registers};
+pub use render::{Brightness, Persistence, Phosphor, FULL_BRIGHTNESS};
+pub use rom::{
+    analyze, detect_variant, reachable_instructions, Analysis, Family, Variant, ROM_START,
+};
+pub use sound::{Tone, ToneGenerator, Waveform};
+pub use utils::{bcd, stretch_u16};
+pub use vram::{Framebuffer, HiresVram, Row, Vram};
+pub use wav::{AudioRecorder, WavWriter, RECORDING_SAMPLE_RATE};
 
-pub fn run(rom: Vec<u8>, platform: Box<dyn Platform>, debug: bool) {
-    let mut emulator = emulator::Emulator::new(rom, platform, debug);
+pub fn run(
+    rom: Vec<u8>,
+    peripherals: Peripherals,
+    settings: Settings,
+    debug: bool,
+) -> Result<(), EmulatorError> {
+    let mut emulator = emulator::Emulator::new(rom, peripherals, settings, debug)?;
     emulator.start();
+
+    Ok(())
 }
 
 pub fn disassemble(rom: Vec<u8>) -> Result<Vec<OpCode>, EmulatorError> {
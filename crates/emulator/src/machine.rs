@@ -0,0 +1,209 @@
+use std::fmt;
+
+use crate::error::EmulatorError;
+use crate::opcode::mnemonic;
+use crate::registers::{Reg, Registers};
+use crate::rom::ROM_START;
+use crate::Vram;
+
+pub const MEMORY_SIZE: usize = 4096;
+
+/// The state of the CHIP-8 machine itself: memory, registers, the call stack and
+/// the screen. Reading it is free, changing it goes through a few checked
+/// mutators, so tools like debuggers and cheats can be built on top.
+#[derive(Clone, Debug)]
+pub struct Machine {
+    // 0x000 -> 0x1FF = interpter
+    // 0x050 -> 0x0A0 = pixel font
+    // 0x200 -> 0xFFF = rom and everything else
+    pub(crate) memory: [u8; MEMORY_SIZE], // 4 KB of memory that lives for the entire program
+    pub(crate) registers: Registers,
+    pub(crate) stack: Vec<u16>,
+    pub(crate) vram: Vram,
+}
+
+impl Machine {
+    pub(crate) fn new() -> Self {
+        Self {
+            memory: [0; MEMORY_SIZE],
+            registers: Registers::new(),
+            stack: Vec::new(),
+            vram: Vram::new(),
+        }
+    }
+
+    pub fn memory(&self) -> &[u8; MEMORY_SIZE] {
+        &self.memory
+    }
+
+    /// PC, I, V0-VF and the timers
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Return addresses of the subroutines being run, innermost last. With the
+    /// stack in memory these are the addresses as they were pushed.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn framebuffer(&self) -> &Vram {
+        &self.vram
+    }
+
+    /// The two bytes at `address` as one instruction, wrapping at the end of memory
+    pub fn instruction_at(&self, address: u16) -> u16 {
+        let address = address as usize % MEMORY_SIZE;
+        u16::from_be_bytes([
+            self.memory[address],
+            self.memory[(address + 1) % MEMORY_SIZE],
+        ])
+    }
+
+    pub fn poke(&mut self, address: u16, value: u8) -> Result<(), EmulatorError> {
+        let byte = self
+            .memory
+            .get_mut(address as usize)
+            .ok_or(EmulatorError::InvalidAddress(address))?;
+        *byte = value;
+
+        Ok(())
+    }
+
+    /// Set one of V0-VF or the timers
+    pub fn set_register(&mut self, reg: Reg, value: u8) {
+        self.registers.set(reg, value);
+    }
+
+    /// Point I at `address`, which has to be in memory
+    pub fn set_i(&mut self, address: u16) -> Result<(), EmulatorError> {
+        if address as usize >= MEMORY_SIZE {
+            return Err(EmulatorError::InvalidAddress(address));
+        }
+        self.registers.set_i(address);
+
+        Ok(())
+    }
+
+    /// Continue from `address`, which has to leave room for a whole instruction
+    pub fn set_pc(&mut self, address: u16) -> Result<(), EmulatorError> {
+        if address as usize + 1 >= MEMORY_SIZE {
+            return Err(EmulatorError::InvalidAddress(address));
+        }
+        self.registers.goto(address);
+
+        Ok(())
+    }
+
+    fn frame(&self, address: u16, subroutine: Option<u16>) -> Frame {
+        Frame {
+            address,
+            instruction: self.instruction_at(address),
+            subroutine,
+        }
+    }
+
+    /// The subroutines being run, innermost first, with `address` the
+    /// instruction the innermost one is at. The rest are at the calls they made.
+    pub fn call_stack(&self, address: u16) -> Vec<Frame> {
+        // a return address follows the call that pushed it, which names the
+        // subroutine the frame inside it runs
+        let called = |call: u16| match self.instruction_at(call) {
+            instruction if instruction & 0xF000 == 0x2000 => Some(instruction & 0x0FFF),
+            _ => None,
+        };
+
+        let mut frames = Vec::new();
+        let mut address = address;
+        for call in self.stack.iter().rev().map(|ret| ret.wrapping_sub(2)) {
+            frames.push(self.frame(address, called(call)));
+            address = call;
+        }
+        frames.push(self.frame(address, Some(ROM_START)));
+        frames
+    }
+}
+
+/// One subroutine on the call stack, written as `sub_300+0A  30A: DRW V0, V1, 15`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub address: u16,
+    pub instruction: u16,
+    /// Where the subroutine starts, unless the call to it was written over
+    pub subroutine: Option<u16>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self.subroutine {
+            Some(start) => {
+                let name = match start {
+                    ROM_START => "start".to_string(),
+                    _ => format!("sub_{:03X}", start),
+                };
+                match self.address.checked_sub(start) {
+                    Some(0) => name,
+                    Some(offset) => format!("{}+{:02X}", name, offset),
+                    None => format!("{}-{:02X}", name, start - self.address),
+                }
+            }
+            None => "?".to_string(),
+        };
+        write!(
+            f,
+            "{:<12}{:03X}: {}",
+            symbol,
+            self.address,
+            mnemonic(self.instruction)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::Machine;
+    use crate::registers::Reg;
+
+    #[test]
+    fn mutators_check_addresses() {
+        let mut machine = Machine::new();
+
+        machine.poke(0x300, 0xAB).unwrap();
+        assert_eq!(machine.memory()[0x300], 0xAB);
+        assert!(machine.poke(0x1000, 0xAB).is_err());
+
+        machine.set_pc(0x400).unwrap();
+        assert_eq!(machine.registers().pc(), 0x400);
+        assert!(machine.set_pc(0xFFF).is_err());
+
+        machine.set_i(0xFFF).unwrap();
+        assert_eq!(machine.registers().get_i(), 0xFFF);
+        assert!(machine.set_i(0x1000).is_err());
+
+        machine.set_register(Reg::VA, 7);
+        assert_eq!(machine.registers().get(Reg::VA), 7);
+    }
+
+    #[test]
+    fn call_stacks_name_the_subroutines() {
+        let mut machine = Machine::new();
+        for (address, value) in [(0x202, 0x23), (0x203, 0x00), (0x304, 0x24), (0x305, 0x00)] {
+            machine.poke(address, value).unwrap();
+        }
+        machine.stack = vec![0x204, 0x306];
+
+        let frames: Vec<String> = machine
+            .call_stack(0x40A)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                "sub_400+0A  40A: DW 0000",
+                "sub_300+04  304: CALL 400",
+                "start+02    202: CALL 300",
+            ]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::machine::Machine;
+use crate::registers::Reg;
+
+/// Gets told what the emulated program does, as it happens. Every method does
+/// nothing by default, so an observer only implements the events it cares about.
+///
+/// Observers are added with [`Emulator::add_observer`](crate::Emulator::add_observer).
+/// Without any, the emulator skips the bookkeeping for them entirely.
+pub trait Observer {
+    /// `instruction` at `address` is about to run
+    fn before_instruction(&mut self, _machine: &Machine, _address: u16, _instruction: u16) {}
+
+    /// `instruction` at `address` has run
+    fn after_instruction(&mut self, _machine: &Machine, _address: u16, _instruction: u16) {}
+
+    /// A program read of memory, for a sprite or FX65
+    fn memory_read(&mut self, _address: u16, _value: u8) {}
+
+    /// A program write to memory, by FX33 or FX55
+    fn memory_write(&mut self, _address: u16, _old: u8, _new: u8) {}
+
+    /// A DXYN drew `height` rows from `sprite` at (`x`, `y`)
+    fn draw(&mut self, _sprite: u16, _x: u8, _y: u8, _height: u8, _collision: bool) {}
+
+    /// A 2NNN at `from` called the subroutine at `to`
+    fn call(&mut self, _from: u16, _to: u16) {}
+
+    /// A 00EE at `from` returned to `to`
+    fn ret(&mut self, _from: u16, _to: u16) {}
+
+    /// The program checked whether `key` is pressed
+    fn key_query(&mut self, _key: u8, _pressed: bool) {}
+
+    /// FX15 or FX18 set `timer` to `value`
+    fn timer_write(&mut self, _timer: Reg, _value: u8) {}
+}
+
+// Lets the frontend keep a handle on an observer it gave away
+
+impl<T: Observer + ?Sized> Observer for Rc<RefCell<T>> {
+    fn before_instruction(&mut self, machine: &Machine, address: u16, instruction: u16) {
+        self.borrow_mut()
+            .before_instruction(machine, address, instruction);
+    }
+
+    fn after_instruction(&mut self, machine: &Machine, address: u16, instruction: u16) {
+        self.borrow_mut()
+            .after_instruction(machine, address, instruction);
+    }
+
+    fn memory_read(&mut self, address: u16, value: u8) {
+        self.borrow_mut().memory_read(address, value);
+    }
+
+    fn memory_write(&mut self, address: u16, old: u8, new: u8) {
+        self.borrow_mut().memory_write(address, old, new);
+    }
+
+    fn draw(&mut self, sprite: u16, x: u8, y: u8, height: u8, collision: bool) {
+        self.borrow_mut().draw(sprite, x, y, height, collision);
+    }
+
+    fn call(&mut self, from: u16, to: u16) {
+        self.borrow_mut().call(from, to);
+    }
+
+    fn ret(&mut self, from: u16, to: u16) {
+        self.borrow_mut().ret(from, to);
+    }
+
+    fn key_query(&mut self, key: u8, pressed: bool) {
+        self.borrow_mut().key_query(key, pressed);
+    }
+
+    fn timer_write(&mut self, timer: Reg, value: u8) {
+        self.borrow_mut().timer_write(timer, value);
+    }
+}
@@ -1,5 +1,7 @@
 #![allow(clippy::upper_case_acronyms)]
 
+use std::fmt;
+
 use crate::{error::EmulatorError, registers::Reg, utils::stretch_u16};
 #[derive(Debug)]
 pub enum OpCode {
@@ -44,7 +46,6 @@ impl TryInto<OpCode> for u16 {
     fn try_into(self) -> Result<OpCode, Self::Error> {
         let parts = stretch_u16(self);
 
-        println!("raw code = {:?}", parts);
         match parts {
             [0x0, 0x0, 0xe, 0x0] => Ok(OpCode::_00E0),
             [0x0, 0x0, 0xe, 0xe] => Ok(OpCode::_00EE),
@@ -225,3 +226,68 @@ impl TryInto<OpCode> for u16 {
         }
     }
 }
+
+/// The usual assembler notation, like `LD V0, 05` or `DRW V1, V2, 5`
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpCode::_00E0 => write!(f, "CLS"),
+            OpCode::_00EE => write!(f, "RET"),
+            OpCode::_1NNN(nnn) => write!(f, "JP {:03X}", nnn),
+            OpCode::_2NNN(nnn) => write!(f, "CALL {:03X}", nnn),
+            OpCode::_3XNN { reg, value } => write!(f, "SE {:?}, {:02X}", reg, value),
+            OpCode::_4XNN { reg, value } => write!(f, "SNE {:?}, {:02X}", reg, value),
+            OpCode::_5XY0 { x, y } => write!(f, "SE {:?}, {:?}", x, y),
+            OpCode::_6XNN { reg, value } => write!(f, "LD {:?}, {:02X}", reg, value),
+            OpCode::_7XNN { reg, value } => write!(f, "ADD {:?}, {:02X}", reg, value),
+            OpCode::_8XY0 { x, y } => write!(f, "LD {:?}, {:?}", x, y),
+            OpCode::_8XY1 { x, y } => write!(f, "OR {:?}, {:?}", x, y),
+            OpCode::_8XY2 { x, y } => write!(f, "AND {:?}, {:?}", x, y),
+            OpCode::_8XY3 { x, y } => write!(f, "XOR {:?}, {:?}", x, y),
+            OpCode::_8XY4 { x, y } => write!(f, "ADD {:?}, {:?}", x, y),
+            OpCode::_8XY5 { x, y } => write!(f, "SUB {:?}, {:?}", x, y),
+            OpCode::_8XY6 { x, y } => write!(f, "SHR {:?}, {:?}", x, y),
+            OpCode::_8XY7 { x, y } => write!(f, "SUBN {:?}, {:?}", x, y),
+            OpCode::_8XYE { x, y } => write!(f, "SHL {:?}, {:?}", x, y),
+            OpCode::_9XY0 { x, y } => write!(f, "SNE {:?}, {:?}", x, y),
+            OpCode::ANNN(nnn) => write!(f, "LD I, {:03X}", nnn),
+            OpCode::BNNN(nnn) => write!(f, "JP V0, {:03X}", nnn),
+            OpCode::CXNN { reg, value } => write!(f, "RND {:?}, {:02X}", reg, value),
+            OpCode::DXYN { x, y, height } => write!(f, "DRW {:?}, {:?}, {}", x, y, height),
+            OpCode::EX9E(reg) => write!(f, "SKP {:?}", reg),
+            OpCode::EXA1(reg) => write!(f, "SKNP {:?}", reg),
+            OpCode::FX07(reg) => write!(f, "LD {:?}, DT", reg),
+            OpCode::FX0A(reg) => write!(f, "LD {:?}, K", reg),
+            OpCode::FX15(reg) => write!(f, "LD DT, {:?}", reg),
+            OpCode::FX18(reg) => write!(f, "LD ST, {:?}", reg),
+            OpCode::FX1E(reg) => write!(f, "ADD I, {:?}", reg),
+            OpCode::FX29(reg) => write!(f, "LD F, {:?}", reg),
+            OpCode::FX33(reg) => write!(f, "LD B, {:?}", reg),
+            OpCode::FX55(reg) => write!(f, "LD [I], {:?}", reg),
+            OpCode::FX65(reg) => write!(f, "LD {:?}, [I]", reg),
+        }
+    }
+}
+
+/// `instruction` in assembler notation, or as a data word if it isn't one
+pub fn mnemonic(instruction: u16) -> String {
+    let opcode: Result<OpCode, _> = instruction.try_into();
+    match opcode {
+        Ok(opcode) => opcode.to_string(),
+        Err(_) => format!("DW {:04X}", instruction),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opcode::mnemonic;
+
+    #[test]
+    fn mnemonics_use_the_usual_notation() {
+        assert_eq!(mnemonic(0x6A05), "LD VA, 05");
+        assert_eq!(mnemonic(0xD125), "DRW V1, V2, 5");
+        assert_eq!(mnemonic(0xF355), "LD [I], V3");
+        assert_eq!(mnemonic(0x2ABC), "CALL ABC");
+        assert_eq!(mnemonic(0xFFFF), "DW FFFF");
+    }
+}
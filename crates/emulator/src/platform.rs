@@ -1,14 +1,178 @@
-use crate::{error::EmulatorError, Vram};
+use std::{cell::RefCell, rc::Rc};
 
+use crate::{error::EmulatorError, Cheat, Machine, Settings, Vram};
 
-pub trait Platform {
-    fn draw(&mut self, vram: Vram);
+/// Presents the framebuffer to the user
+pub trait Display {
+    fn draw(&mut self, vram: &Vram);
 
+    /// Look at the whole machine, every frame before it is drawn. For frontends
+    /// that show more than the screen, like a debugger.
+    fn inspect(&mut self, _machine: &Machine) {}
+}
+
+/// Drives the single tone beeper
+pub trait Audio {
     fn start_beep(&mut self);
     fn stop_beep(&mut self);
 
+    /// Called once at the end of every emulated frame
+    fn end_frame(&mut self) {}
+}
+
+/// Source of CHIP-8 keypad state
+pub trait Input {
     fn scan_keys(&mut self);
     fn key_is_pressed(&self, key: &u8) -> bool;
-    fn should_quit(&mut self) -> bool;
+    /// Block until a key is pressed. Only debug stepping uses this, FX0A polls
+    /// `key_is_pressed` so the machine keeps running while it waits.
     fn await_keypress(&mut self) -> Result<u8, EmulatorError>;
 }
+
+/// Emulation controls the host can ask for, e.g. through hotkeys
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostCommand {
+    TogglePause,
+    /// Run a single frame, pausing first if running
+    FrameAdvance,
+    /// Restart the rom, see [`Emulator::reset`](crate::Emulator::reset)
+    Reset,
+    /// Restart the rom with memory cleared
+    HardReset,
+    /// Switch to another rom, run with `settings`
+    LoadRom {
+        rom: Vec<u8>,
+        settings: Settings,
+    },
+    ToggleFastForward,
+    ToggleSlowMotion,
+    /// Save the machine to a state slot, from 0 to `STATE_SLOTS - 1`
+    SaveState(usize),
+    LoadState(usize),
+    /// Pause when the program is about to run the instruction at this address,
+    /// or stop doing so
+    ToggleBreakpoint(u16),
+    /// Change a byte of memory
+    Poke {
+        address: u16,
+        value: u8,
+    },
+    /// Replace the cheats applied every frame
+    SetCheats(Vec<Cheat>),
+}
+
+/// Requests coming from the host rather than the emulated machine
+pub trait HostControl {
+    fn should_quit(&mut self) -> bool;
+
+    /// The next pending command, polled until empty at the start of every frame
+    fn next_command(&mut self) -> Option<HostCommand> {
+        None
+    }
+
+    /// Whether frames should be paced to real time, rather than run as fast as possible
+    fn throttle(&self) -> bool {
+        true
+    }
+}
+
+/// A single object that provides every peripheral the emulator needs.
+///
+/// Anything implementing the four peripheral traits is a `Platform`, and can be
+/// turned into [`Peripherals`] with [`Peripherals::from_platform`].
+pub trait Platform: Display + Audio + Input + HostControl {}
+
+impl<T: Display + Audio + Input + HostControl> Platform for T {}
+
+/// The set of peripherals an emulator is composed of. Each one can be swapped
+/// independently, e.g. to silence audio in tests or to pair a display with a
+/// different input source.
+pub struct Peripherals {
+    pub display: Box<dyn Display>,
+    pub audio: Box<dyn Audio>,
+    pub input: Box<dyn Input>,
+    pub host: Box<dyn HostControl>,
+}
+
+impl Peripherals {
+    pub fn new(
+        display: Box<dyn Display>,
+        audio: Box<dyn Audio>,
+        input: Box<dyn Input>,
+        host: Box<dyn HostControl>,
+    ) -> Self {
+        Self {
+            display,
+            audio,
+            input,
+            host,
+        }
+    }
+
+    /// Split a monolithic platform into its peripherals, which all share the same
+    /// underlying object
+    pub fn from_platform<P: Platform + 'static>(platform: P) -> Self {
+        let shared = Rc::new(RefCell::new(platform));
+
+        Self {
+            display: Box::new(Rc::clone(&shared)),
+            audio: Box::new(Rc::clone(&shared)),
+            input: Box::new(Rc::clone(&shared)),
+            host: Box::new(shared),
+        }
+    }
+}
+
+// Adapters so a single shared object can back several peripherals at once
+
+impl<T: Display + ?Sized> Display for Rc<RefCell<T>> {
+    fn draw(&mut self, vram: &Vram) {
+        self.borrow_mut().draw(vram);
+    }
+
+    fn inspect(&mut self, machine: &Machine) {
+        self.borrow_mut().inspect(machine);
+    }
+}
+
+impl<T: Audio + ?Sized> Audio for Rc<RefCell<T>> {
+    fn start_beep(&mut self) {
+        self.borrow_mut().start_beep();
+    }
+
+    fn stop_beep(&mut self) {
+        self.borrow_mut().stop_beep();
+    }
+
+    fn end_frame(&mut self) {
+        self.borrow_mut().end_frame();
+    }
+}
+
+impl<T: Input + ?Sized> Input for Rc<RefCell<T>> {
+    fn scan_keys(&mut self) {
+        self.borrow_mut().scan_keys();
+    }
+
+    fn key_is_pressed(&self, key: &u8) -> bool {
+        self.borrow().key_is_pressed(key)
+    }
+
+    fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
+        self.borrow_mut().await_keypress()
+    }
+}
+
+impl<T: HostControl + ?Sized> HostControl for Rc<RefCell<T>> {
+    fn should_quit(&mut self) -> bool {
+        self.borrow_mut().should_quit()
+    }
+
+    fn next_command(&mut self) -> Option<HostCommand> {
+        self.borrow_mut().next_command()
+    }
+
+    fn throttle(&self) -> bool {
+        self.borrow().throttle()
+    }
+}
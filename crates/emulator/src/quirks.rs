@@ -0,0 +1,190 @@
+use crate::rom::Variant;
+
+// Instructions executed per frame
+pub const DEFAULT_TICKRATE: usize = 10;
+
+// Nested calls the COSMAC VIP interpreter had room for, and SCHIP
+const VIP_STACK_DEPTH: usize = 12;
+const SCHIP_STACK_DEPTH: usize = 16;
+
+/// Behaviours that differ between CHIP-8 interpreters, named after the quirks in
+/// the community chip-8-database. The default is how chipmunk has always behaved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX in place, instead of shifting VY into VX
+    pub shift: bool,
+    /// FX55/FX65 increment I by X, instead of X + 1
+    pub memory_increment_by_x: bool,
+    /// FX55/FX65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the screen, instead of being clipped
+    pub wrap: bool,
+    /// BNNN jumps to XNN + VX, instead of NNN + V0
+    pub jump: bool,
+    /// DXYN waits for the next frame before going on
+    pub vblank: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+/// One of the [`Quirks`], for going over them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quirk {
+    Shift,
+    MemoryIncrementByX,
+    MemoryLeaveIUnchanged,
+    Wrap,
+    Jump,
+    Vblank,
+    Logic,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 7] = [
+        Quirk::Shift,
+        Quirk::MemoryIncrementByX,
+        Quirk::MemoryLeaveIUnchanged,
+        Quirk::Wrap,
+        Quirk::Jump,
+        Quirk::Vblank,
+        Quirk::Logic,
+    ];
+
+    /// The name the chip-8-database uses
+    pub fn name(self) -> &'static str {
+        match self {
+            Quirk::Shift => "shift",
+            Quirk::MemoryIncrementByX => "memoryIncrementByX",
+            Quirk::MemoryLeaveIUnchanged => "memoryLeaveIUnchanged",
+            Quirk::Wrap => "wrap",
+            Quirk::Jump => "jump",
+            Quirk::Vblank => "vblank",
+            Quirk::Logic => "logic",
+        }
+    }
+}
+
+impl Quirks {
+    fn field(&mut self, quirk: Quirk) -> &mut bool {
+        match quirk {
+            Quirk::Shift => &mut self.shift,
+            Quirk::MemoryIncrementByX => &mut self.memory_increment_by_x,
+            Quirk::MemoryLeaveIUnchanged => &mut self.memory_leave_i_unchanged,
+            Quirk::Wrap => &mut self.wrap,
+            Quirk::Jump => &mut self.jump,
+            Quirk::Vblank => &mut self.vblank,
+            Quirk::Logic => &mut self.logic,
+        }
+    }
+
+    pub fn get(mut self, quirk: Quirk) -> bool {
+        *self.field(quirk)
+    }
+
+    pub fn set(&mut self, quirk: Quirk, on: bool) {
+        *self.field(quirk) = on;
+    }
+
+    /// The quirks that are on
+    pub fn enabled(self) -> impl Iterator<Item = Quirk> {
+        Quirk::ALL.into_iter().filter(move |quirk| self.get(*quirk))
+    }
+
+    /// The quirks of the interpreter each dialect is usually run on: the COSMAC VIP,
+    /// SCHIP 1.1 and Octo
+    pub fn for_variant(variant: Variant) -> Self {
+        match variant {
+            Variant::Chip8 => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: false,
+                jump: false,
+                vblank: true,
+                logic: true,
+            },
+            Variant::SuperChip => Self {
+                shift: true,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: true,
+                wrap: false,
+                jump: true,
+                vblank: false,
+                logic: false,
+            },
+            Variant::XoChip => Self {
+                shift: false,
+                memory_increment_by_x: false,
+                memory_leave_i_unchanged: false,
+                wrap: true,
+                jump: false,
+                vblank: false,
+                logic: false,
+            },
+        }
+    }
+}
+
+/// Where subroutine calls keep their return addresses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stack {
+    /// Nested calls there is room for, going deeper is an error
+    pub depth: usize,
+    /// Keep the return addresses in the top of memory like the VIP did, so a rom
+    /// writing over them returns somewhere else
+    pub in_memory: bool,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::for_variant(Variant::SuperChip)
+    }
+}
+
+impl Stack {
+    pub fn for_variant(variant: Variant) -> Self {
+        match variant {
+            Variant::Chip8 => Self {
+                depth: VIP_STACK_DEPTH,
+                in_memory: true,
+            },
+            Variant::SuperChip | Variant::XoChip => Self {
+                depth: SCHIP_STACK_DEPTH,
+                in_memory: false,
+            },
+        }
+    }
+}
+
+/// How a rom wants to be run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    /// Instructions executed per frame
+    pub tickrate: usize,
+    pub quirks: Quirks,
+    pub stack: Stack,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tickrate: DEFAULT_TICKRATE,
+            quirks: Quirks::default(),
+            stack: Stack::default(),
+        }
+    }
+}
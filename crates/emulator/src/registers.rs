@@ -1,4 +1,4 @@
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Reg {
     V0,
     V1,
@@ -100,7 +100,7 @@ impl From<usize> for Reg {
 const TIMER_COUNT: usize = 2;
 const REG_COUNT: usize = 16 + TIMER_COUNT;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Registers {
     inner: [u8; REG_COUNT],
     // default: used as carry flag
@@ -113,6 +113,12 @@ pub struct Registers {
     i: u16,
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Self {
         let inner = [0; REG_COUNT];
@@ -0,0 +1,167 @@
+use std::{fmt, str::FromStr};
+
+use crate::{Vram, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Brightness of every pixel, from 0 (off) to 255 (fully lit)
+pub type Brightness = [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+
+pub const FULL_BRIGHTNESS: u8 = u8::MAX;
+
+/// How the previous frames bleed into the current one. CHIP-8 games erase and
+/// redraw sprites with XOR, which flickers badly unless it's smoothed over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Persistence {
+    /// Show the framebuffer as is
+    #[default]
+    Off,
+    /// Pixels that turn off fade out over the given amount of frames
+    Decay(u8),
+    /// A pixel is lit if it was lit in either of the last two frames
+    Blend,
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    /// Parses `off`, `blend`, `decay` or `decay:<frames>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "off" => Ok(Persistence::Off),
+            None if s == "blend" => Ok(Persistence::Blend),
+            None if s == "decay" => Ok(Persistence::Decay(Phosphor::DEFAULT_DECAY_FRAMES)),
+            Some(("decay", frames)) => match frames.parse() {
+                Ok(0) | Err(_) => Err(format!("'{}' is not a positive amount of frames", frames)),
+                Ok(frames) => Ok(Persistence::Decay(frames)),
+            },
+            _ => Err(format!(
+                "unknown persistence mode '{}', expected off, blend, decay or decay:<frames>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Persistence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Persistence::Off => write!(f, "off"),
+            Persistence::Decay(frames) => write!(f, "decay:{}", frames),
+            Persistence::Blend => write!(f, "blend"),
+        }
+    }
+}
+
+/// Rendering stage that sits between the emulator framebuffer and a frontend's
+/// output. Feed it every presented frame and draw the brightness it returns.
+pub struct Phosphor {
+    mode: Persistence,
+    previous: Vram,
+    brightness: Brightness,
+}
+
+impl Phosphor {
+    pub const DEFAULT_DECAY_FRAMES: u8 = 4;
+
+    pub fn new(mode: Persistence) -> Self {
+        Self {
+            mode,
+            previous: Vram::new(),
+            brightness: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+        }
+    }
+
+    pub fn mode(&self) -> Persistence {
+        self.mode
+    }
+
+    pub fn apply(&mut self, vram: &Vram) -> &Brightness {
+        match self.mode {
+            Persistence::Off => self.fill(vram),
+            Persistence::Blend => {
+                let mut blended = *vram;
+                blended.merge(&self.previous);
+                self.fill(&blended);
+            }
+            Persistence::Decay(frames) => {
+                // round up, so a pixel is fully dark after exactly `frames` frames
+                let step = FULL_BRIGHTNESS.div_ceil(frames.max(1));
+
+                for (y, row) in self.brightness.iter_mut().enumerate() {
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        *pixel = if vram.pixel(x, y) {
+                            FULL_BRIGHTNESS
+                        } else {
+                            pixel.saturating_sub(step)
+                        };
+                    }
+                }
+            }
+        }
+
+        self.previous = *vram;
+        &self.brightness
+    }
+
+    fn fill(&mut self, vram: &Vram) {
+        for (y, row) in self.brightness.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = if vram.pixel(x, y) { FULL_BRIGHTNESS } else { 0 };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{Persistence, Phosphor, FULL_BRIGHTNESS};
+    use crate::Vram;
+
+    fn lit(x: usize, y: usize) -> Vram {
+        let mut vram = Vram::new();
+        vram.draw_sprite(x, y, &[0x80]);
+        vram
+    }
+
+    #[test]
+    fn off_shows_the_framebuffer() {
+        let mut phosphor = Phosphor::new(Persistence::Off);
+
+        assert_eq!(phosphor.apply(&lit(1, 1))[1][1], FULL_BRIGHTNESS);
+        assert_eq!(phosphor.apply(&Vram::new())[1][1], 0);
+    }
+
+    #[test]
+    fn blend_keeps_the_last_frame() {
+        let mut phosphor = Phosphor::new(Persistence::Blend);
+        phosphor.apply(&lit(1, 1));
+
+        let brightness = phosphor.apply(&lit(2, 1));
+        assert_eq!(brightness[1][1], FULL_BRIGHTNESS);
+        assert_eq!(brightness[1][2], FULL_BRIGHTNESS);
+
+        assert_eq!(phosphor.apply(&Vram::new())[1][1], 0);
+    }
+
+    #[test]
+    fn decay_fades_out() {
+        let mut phosphor = Phosphor::new(Persistence::Decay(2));
+        phosphor.apply(&lit(0, 0));
+
+        let first = phosphor.apply(&Vram::new())[0][0];
+        assert!(first > 0 && first < FULL_BRIGHTNESS);
+        assert_eq!(phosphor.apply(&Vram::new())[0][0], 0);
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("off".parse(), Ok(Persistence::Off));
+        assert_eq!("blend".parse(), Ok(Persistence::Blend));
+        assert_eq!(
+            "decay".parse(),
+            Ok(Persistence::Decay(Phosphor::DEFAULT_DECAY_FRAMES))
+        );
+        assert_eq!("decay:8".parse(), Ok(Persistence::Decay(8)));
+        assert!("decay:0".parse::<Persistence>().is_err());
+        assert!("glow".parse::<Persistence>().is_err());
+    }
+}
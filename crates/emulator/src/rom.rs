@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::utils::stretch_u16;
+
+/// The CHIP-8 dialect a rom was written for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Chip8 => write!(f, "CHIP-8"),
+            Variant::SuperChip => write!(f, "SCHIP"),
+            Variant::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+/// The dialect an instruction belongs to, for the ones the extensions added
+fn variant_of(raw: u16) -> Variant {
+    match stretch_u16(raw) {
+        // scroll left/right, exit, lores/hires, big sprites, big font and flags
+        [0x0, 0x0, 0xc, n] if n > 0 => Variant::SuperChip,
+        [0x0, 0x0, 0xf, 0xb..=0xf] => Variant::SuperChip,
+        [0xd, _, _, 0x0] => Variant::SuperChip,
+        [0xf, _, 0x3, 0x0] | [0xf, _, 0x7, 0x5] | [0xf, _, 0x8, 0x5] => Variant::SuperChip,
+        // scroll up, register ranges, long I, planes, audio and pitch
+        [0x0, 0x0, 0xd, n] if n > 0 => Variant::XoChip,
+        [0x5, _, _, 0x2] | [0x5, _, _, 0x3] => Variant::XoChip,
+        [0xf, 0x0, 0x0, 0x0] | [0xf, _, 0x0, 0x1] | [0xf, 0x0, 0x0, 0x2] => Variant::XoChip,
+        [0xf, _, 0x3, 0xa] => Variant::XoChip,
+        _ => Variant::Chip8,
+    }
+}
+
+/// Groups of instructions, by who added them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Family {
+    Base,
+    SuperChip,
+    XoChip,
+    /// 0NNN, calls into the host machine's own code that nothing emulates
+    MachineCall,
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Family::Base => write!(f, "CHIP-8"),
+            Family::SuperChip => write!(f, "SCHIP"),
+            Family::XoChip => write!(f, "XO-CHIP"),
+            Family::MachineCall => write!(f, "0NNN machine calls"),
+        }
+    }
+}
+
+fn family_of(raw: u16) -> Family {
+    match variant_of(raw) {
+        Variant::SuperChip => Family::SuperChip,
+        Variant::XoChip => Family::XoChip,
+        Variant::Chip8 => match raw {
+            0x00E0 | 0x00EE => Family::Base,
+            0x0000..=0x0FFF => Family::MachineCall,
+            _ => Family::Base,
+        },
+    }
+}
+
+/// Where a rom is loaded in memory
+pub const ROM_START: u16 = 0x200;
+
+/// The instruction at `address`, if it's inside the rom
+pub(crate) fn word_at(rom: &[u8], address: u16) -> Option<u16> {
+    let offset = address.checked_sub(ROM_START)? as usize;
+    let bytes = rom.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Addresses of the instructions reachable from the start of the rom, found by
+/// following jumps, calls and skips. Computed jumps (BNNN) can't be followed, so
+/// code only reached through them is missed.
+pub fn reachable_instructions(rom: &[u8]) -> BTreeSet<u16> {
+    flood(rom, ROM_START, true).0
+}
+
+/// The instructions reachable from `start`, and the subroutines called on the way.
+/// Without `follow_calls`, calls are assumed to return and only the code after
+/// them is followed.
+fn flood(rom: &[u8], start: u16, follow_calls: bool) -> (BTreeSet<u16>, BTreeSet<u16>) {
+    let mut seen = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut pending = vec![start];
+
+    while let Some(address) = pending.pop() {
+        let Some(raw) = word_at(rom, address) else {
+            continue;
+        };
+        if !seen.insert(address) {
+            continue;
+        }
+
+        let next = address.wrapping_add(2);
+        let nnn = raw & 0x0FFF;
+        match stretch_u16(raw) {
+            // return, exit
+            [0x0, 0x0, 0xe, 0xe] | [0x0, 0x0, 0xf, 0xd] => {}
+            [0x1, ..] => pending.push(nnn),
+            [0x2, ..] => {
+                calls.insert(nnn);
+                if follow_calls {
+                    pending.push(nnn);
+                }
+                pending.push(next);
+            }
+            [0xb, ..] => {}
+            // XO-CHIP's long I load is followed by its 16 bit address
+            [0xf, 0x0, 0x0, 0x0] => pending.push(next.wrapping_add(2)),
+            [0x3, ..]
+            | [0x4, ..]
+            | [0x5, ..]
+            | [0x9, ..]
+            | [0xe, _, 0x9, 0xe]
+            | [0xe, _, 0xa, 0x1] => pending.extend([next, next.wrapping_add(2)]),
+            _ => pending.push(next),
+        }
+    }
+
+    (seen, calls)
+}
+
+/// Guess the dialect of a rom from the reachable instructions only the extensions
+/// have. It is a hint for showing to the user, not something to rely on.
+pub fn detect_variant(rom: &[u8]) -> Variant {
+    reachable_instructions(rom)
+        .into_iter()
+        .filter_map(|address| word_at(rom, address))
+        .map(variant_of)
+        .max()
+        .unwrap_or_default()
+}
+
+/// How deep the calls from the subroutine at `entry` nest, `None` if they
+/// recurse. Depths are remembered in `depths`.
+fn call_depth(
+    rom: &[u8],
+    entry: u16,
+    depths: &mut BTreeMap<u16, Option<usize>>,
+    visiting: &mut BTreeSet<u16>,
+) -> Option<usize> {
+    if let Some(depth) = depths.get(&entry) {
+        return *depth;
+    }
+    if !visiting.insert(entry) {
+        return None;
+    }
+
+    let mut depth = Some(0);
+    for callee in flood(rom, entry, false).1 {
+        let callee_depth = call_depth(rom, callee, depths, visiting);
+        depth = depth
+            .zip(callee_depth)
+            .map(|(depth, callee)| depth.max(callee + 1));
+    }
+
+    visiting.remove(&entry);
+    depths.insert(entry, depth);
+    depth
+}
+
+/// What can be told about a rom without running it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Analysis {
+    pub families: BTreeSet<Family>,
+    pub variant: Variant,
+    /// Whether the rom stores registers (FX55) or BCD digits (FX33) and points I
+    /// inside itself. I can be moved in ways that can't be followed, so this is a
+    /// guess.
+    pub writes_to_itself: bool,
+    /// The deepest nesting of subroutine calls, `None` if they recurse
+    pub max_stack_depth: Option<usize>,
+}
+
+/// Decode the reachable instructions of `rom`, see [`reachable_instructions`]
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let words: Vec<u16> = reachable_instructions(rom)
+        .into_iter()
+        .filter_map(|address| word_at(rom, address))
+        .collect();
+
+    let rom_end = ROM_START as usize + rom.len();
+    let stores = words
+        .iter()
+        .any(|raw| matches!(stretch_u16(*raw), [0xf, _, 0x5, 0x5] | [0xf, _, 0x3, 0x3]));
+    let points_inside = words.iter().any(|raw| {
+        let address = (raw & 0x0FFF) as usize;
+        raw >> 12 == 0xA && (ROM_START as usize..rom_end).contains(&address)
+    });
+
+    Analysis {
+        families: words.iter().map(|raw| family_of(*raw)).collect(),
+        variant: words
+            .iter()
+            .map(|raw| variant_of(*raw))
+            .max()
+            .unwrap_or_default(),
+        writes_to_itself: stores && points_inside,
+        max_stack_depth: call_depth(rom, ROM_START, &mut BTreeMap::new(), &mut BTreeSet::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rom::{analyze, detect_variant, reachable_instructions, Family, Variant};
+
+    #[test]
+    fn plain_roms_are_chip8() {
+        // V0 = 5, sound timer = V0, then spin forever
+        let rom = [0x60, 0x05, 0xF0, 0x18, 0x12, 0x04];
+        assert_eq!(detect_variant(&rom), Variant::Chip8);
+        assert_eq!(detect_variant(&[]), Variant::Chip8);
+    }
+
+    #[test]
+    fn extension_instructions_give_the_variant_away() {
+        // hires, then exit
+        assert_eq!(
+            detect_variant(&[0x00, 0xFF, 0x00, 0xFD]),
+            Variant::SuperChip
+        );
+        // a SCHIP scroll and an XO-CHIP long I load
+        assert_eq!(
+            detect_variant(&[0x00, 0xC4, 0xF0, 0x00, 0x03, 0x00, 0x00, 0xFD]),
+            Variant::XoChip
+        );
+    }
+
+    #[test]
+    fn data_after_the_code_is_not_decoded() {
+        // spin forever, followed by a sprite that looks like a hires instruction
+        let rom = [0x12, 0x00, 0x00, 0xFF];
+        assert_eq!(detect_variant(&rom), Variant::Chip8);
+    }
+
+    #[test]
+    fn follows_calls_and_skips() {
+        // 200: call 206, 202: skip if V0 == 0, 204: return, 206: jump 206
+        let rom = [0x22, 0x06, 0x30, 0x00, 0x00, 0xEE, 0x12, 0x06];
+        let reachable: Vec<u16> = reachable_instructions(&rom).into_iter().collect();
+        assert_eq!(reachable, vec![0x200, 0x202, 0x204, 0x206]);
+    }
+
+    #[test]
+    fn analysis_finds_families_and_stack_depth() {
+        // 200: call 208, 202: call 20A, 204: machine call, 206: spin
+        // 208: call 20A, then 20A: return
+        let rom = [
+            0x22, 0x08, 0x22, 0x0A, 0x01, 0x23, 0x12, 0x06, 0x22, 0x0A, 0x00, 0xEE,
+        ];
+        let analysis = analyze(&rom);
+
+        assert_eq!(
+            analysis.families.into_iter().collect::<Vec<_>>(),
+            vec![Family::Base, Family::MachineCall]
+        );
+        assert_eq!(analysis.max_stack_depth, Some(2));
+        assert!(!analysis.writes_to_itself);
+    }
+
+    #[test]
+    fn recursion_has_no_maximum_depth() {
+        // 200: call 202, 202: call 202
+        assert_eq!(analyze(&[0x22, 0x02, 0x22, 0x02]).max_stack_depth, None);
+    }
+
+    #[test]
+    fn storing_through_i_inside_the_rom_is_a_self_write() {
+        // I = 206, BCD of V0 to I, spin, then the digits
+        let rom = [0xA2, 0x06, 0xF0, 0x33, 0x12, 0x04, 0x00, 0x00, 0x00];
+        assert!(analyze(&rom).writes_to_itself);
+    }
+}
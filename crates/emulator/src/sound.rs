@@ -0,0 +1,176 @@
+use std::{f32::consts::TAU, fmt, str::FromStr};
+
+// Fade in/out over a few milliseconds, so starting and stopping doesn't click
+const ENVELOPE_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sine" => Ok(Waveform::Sine),
+            _ => Err(format!(
+                "unknown waveform '{}', expected square, triangle or sine",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Waveform::Square => write!(f, "square"),
+            Waveform::Triangle => write!(f, "triangle"),
+            Waveform::Sine => write!(f, "sine"),
+        }
+    }
+}
+
+/// What the beeper sounds like
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    /// Pitch in Hz
+    pub frequency: f32,
+    /// Amplitude from 0.0 to 1.0
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.05,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+/// Correction for the discontinuity of a naive waveform at phase `t`, which keeps
+/// the harmonics above nyquist from folding back as aliasing (PolyBLEP)
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Generates the beeper's samples, independent of any audio backend. The gate
+/// turns the tone on and off, with a short attack and release.
+pub struct ToneGenerator {
+    waveform: Waveform,
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+
+    // the envelope follows the gate, moving by `envelope_step` each sample
+    gate: bool,
+    envelope: f32,
+    envelope_step: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(tone: Tone, sample_rate: u32) -> Self {
+        Self {
+            waveform: tone.waveform,
+            phase_inc: tone.frequency / sample_rate as f32,
+            phase: 0.0,
+            volume: tone.volume.clamp(0.0, 1.0),
+            gate: false,
+            envelope: 0.0,
+            envelope_step: 1.0 / (ENVELOPE_SECONDS * sample_rate as f32),
+        }
+    }
+
+    pub fn set_gate(&mut self, on: bool) {
+        self.gate = on;
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    fn oscillator(&self) -> f32 {
+        let t = self.phase;
+        let dt = self.phase_inc;
+
+        match self.waveform {
+            Waveform::Square => {
+                let naive = if t < 0.5 { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep((t + 0.5) % 1.0, dt)
+            }
+            // continuous, so its harmonics already fall off fast enough
+            Waveform::Triangle => 1.0 - 4.0 * (t - 0.5).abs(),
+            Waveform::Sine => (TAU * t).sin(),
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let target = if self.gate { 1.0 } else { 0.0 };
+        if self.envelope < target {
+            self.envelope = (self.envelope + self.envelope_step).min(target);
+        } else if self.envelope > target {
+            self.envelope = (self.envelope - self.envelope_step).max(target);
+        }
+
+        let sample = self.oscillator() * self.volume * self.envelope;
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+
+        sample
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = self.next_sample();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sound::{Tone, ToneGenerator, Waveform};
+
+    #[test]
+    fn silent_until_gated() {
+        let mut generator = ToneGenerator::new(Tone::default(), 44100);
+        let mut out = [1.0; 64];
+        generator.fill(&mut out);
+
+        assert!(out.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn ramps_up_and_stays_within_volume() {
+        let tone = Tone {
+            frequency: 440.0,
+            volume: 0.5,
+            waveform: Waveform::Sine,
+        };
+        let mut generator = ToneGenerator::new(tone, 44100);
+        generator.set_gate(true);
+
+        let mut out = [0.0; 4410];
+        generator.fill(&mut out);
+
+        // the attack keeps the first sample quiet
+        assert!(out[1].abs() < 0.01);
+        assert!(out.iter().all(|sample| sample.abs() <= 0.5));
+        assert!(out.iter().any(|sample| sample.abs() > 0.45));
+    }
+}
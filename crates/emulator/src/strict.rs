@@ -0,0 +1,151 @@
+use crate::error::EmulatorError;
+use crate::machine::MEMORY_SIZE;
+use crate::rom::ROM_START;
+
+// What is known about each byte of memory
+const WRITTEN: u8 = 1 << 0; // the font, the rom image, or stored by the rom
+const DATA: u8 = 1 << 1; // read or stored as data by the rom
+
+/// Catches what a rom does that only works by luck on some interpreters, like
+/// running off the end of memory or into its own sprites. Each check names the
+/// instruction that broke it.
+pub(crate) struct Strict {
+    marks: Box<[u8; MEMORY_SIZE]>,
+    // the instruction being checked, for the diagnostic
+    address: u16,
+    instruction: u16,
+}
+
+impl Strict {
+    pub fn new() -> Self {
+        Self {
+            marks: Box::new([0; MEMORY_SIZE]),
+            address: 0,
+            instruction: 0,
+        }
+    }
+
+    /// Forget what the rom did, knowing only the `(start, len)` ranges in
+    /// `loaded` as written
+    pub fn reset(&mut self, loaded: &[(usize, usize)]) {
+        self.marks.fill(0);
+        for &(start, len) in loaded {
+            for mark in &mut self.marks[start..start + len] {
+                *mark |= WRITTEN;
+            }
+        }
+    }
+
+    fn fail(&self, problem: String) -> EmulatorError {
+        EmulatorError::Strict(format!(
+            "{:03X}: {:04X}: {}",
+            self.address, self.instruction, problem
+        ))
+    }
+
+    /// Before running the instruction at `address`
+    pub fn execute(&mut self, address: u16, instruction: u16) -> Result<(), EmulatorError> {
+        self.address = address;
+        self.instruction = instruction;
+
+        if address % 2 == 1 {
+            return Err(self.fail("PC is at an odd address".to_string()));
+        }
+        let bytes = [address as usize, address as usize + 1];
+        if bytes.iter().any(|&byte| byte >= MEMORY_SIZE) {
+            return Err(self.fail("PC ran off the end of memory".to_string()));
+        }
+        if let Some(&byte) = bytes.iter().find(|&&byte| self.marks[byte] & DATA != 0) {
+            return Err(self.fail(format!("running {:03X}, which was used as data", byte)));
+        }
+        if let Some(&byte) = bytes.iter().find(|&&byte| self.marks[byte] & WRITTEN == 0) {
+            return Err(self.fail(format!("running {:03X}, which was never written", byte)));
+        }
+        Ok(())
+    }
+
+    /// I once an instruction has moved it
+    pub fn index(&self, i: usize) -> Result<(), EmulatorError> {
+        if i >= MEMORY_SIZE {
+            return Err(self.fail(format!("I moved past FFF to {:X}", i)));
+        }
+        Ok(())
+    }
+
+    /// The rom reading `len` bytes from `start` as data
+    pub fn read(&mut self, start: usize, len: usize) -> Result<(), EmulatorError> {
+        if start + len > MEMORY_SIZE {
+            return Err(self.fail(format!(
+                "reading {} bytes past FFF",
+                start + len - MEMORY_SIZE
+            )));
+        }
+        for address in start..start + len {
+            if self.marks[address] & WRITTEN == 0 {
+                return Err(self.fail(format!("reading {:03X}, which was never written", address)));
+            }
+            self.marks[address] |= DATA;
+        }
+        Ok(())
+    }
+
+    /// The rom storing `len` bytes from `start`
+    pub fn write(&mut self, start: usize, len: usize) -> Result<(), EmulatorError> {
+        if start + len > MEMORY_SIZE {
+            return Err(self.fail(format!(
+                "writing {} bytes past FFF",
+                start + len - MEMORY_SIZE
+            )));
+        }
+        if start < ROM_START as usize {
+            return Err(self.fail(format!(
+                "writing {:03X}, inside the interpreter below 200",
+                start
+            )));
+        }
+        for mark in &mut self.marks[start..start + len] {
+            *mark |= WRITTEN | DATA;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::EmulatorError;
+    use crate::strict::Strict;
+
+    fn problem(result: Result<(), EmulatorError>) -> String {
+        match result {
+            Err(EmulatorError::Strict(problem)) => problem,
+            other => panic!("expected a strict error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn data_and_code_stay_apart() {
+        let mut strict = Strict::new();
+        strict.reset(&[(0x200, 8)]);
+
+        assert!(strict.execute(0x200, 0xA206).is_ok());
+        assert!(strict.read(0x206, 2).is_ok());
+        assert_eq!(
+            problem(strict.execute(0x206, 0x1234)),
+            "206: 1234: running 206, which was used as data"
+        );
+        assert_eq!(
+            problem(strict.execute(0x209, 0x0000)),
+            "209: 0000: PC is at an odd address"
+        );
+        assert_eq!(
+            problem(strict.read(0x207, 2)),
+            "209: 0000: reading 208, which was never written"
+        );
+
+        assert!(strict.write(0x300, 3).is_ok());
+        assert!(strict.read(0x300, 3).is_ok());
+        assert!(strict.write(0x1FF, 1).is_err());
+        assert!(strict.write(0xFFE, 3).is_err());
+        assert!(strict.index(0x1000).is_err());
+    }
+}
@@ -2,15 +2,17 @@
 ///
 /// Example:
 /// ```
-/// bcd(104) == [1, 0, 4];
-/// bcd(0xFA) == [2, 5, 0];
+/// use chipmunk_backend::bcd;
+///
+/// assert_eq!(bcd(104), [1, 0, 4]);
+/// assert_eq!(bcd(0xFA), [2, 5, 0]);
 /// ```
 pub fn bcd(num: u8) -> [u8; 3] {
     let mut curr = num;
     let mut result = [0, 0, 0];
     for i in 0..3 {
         let digit = curr % 10;
-        result[2 - i] = digit as u8;
+        result[2 - i] = digit;
         curr /= 10;
     }
 
@@ -21,7 +23,9 @@ pub fn bcd(num: u8) -> [u8; 3] {
 ///
 /// Example:
 /// ```
-/// stretch_u16(0x6278) == [0x06, 0x02, 0x07, 0x08];
+/// use chipmunk_backend::stretch_u16;
+///
+/// assert_eq!(stretch_u16(0x6278), [0x06, 0x02, 0x07, 0x08]);
 /// ```
 pub fn stretch_u16(input: u16) -> [u8; 4] {
     let fourth = (input & 0x000F) as u8;
@@ -45,6 +49,6 @@ mod tests {
     #[test]
     fn simple() {
         let actual = stretch_u16(0x6278);
-        assert_eq!(actual, [0x06 as u8, 0x02, 0x07, 0x08]);
+        assert_eq!(actual, [0x06_u8, 0x02, 0x07, 0x08]);
     }
 }
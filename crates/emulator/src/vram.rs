@@ -0,0 +1,201 @@
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+/// A single packed framebuffer row. Column 0 is stored in the most significant bit,
+/// so printing a row in binary shows it left to right.
+pub trait Row:
+    Copy
+    + Default
+    + Eq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+{
+    /// Amount of pixels in a row
+    const WIDTH: usize;
+
+    /// Place an 8 pixel wide sprite row so that its left edge lands on column `x`,
+    /// wrapping around the right edge
+    fn sprite(byte: u8, x: usize) -> Self;
+
+    /// Like [`Row::sprite`], with the pixels past the right edge dropped
+    fn sprite_clipped(byte: u8, x: usize) -> Self;
+
+    fn pixel(self, x: usize) -> bool;
+
+    fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+}
+
+macro_rules! impl_row {
+    ($ty:ty) => {
+        impl Row for $ty {
+            const WIDTH: usize = <$ty>::BITS as usize;
+
+            fn sprite(byte: u8, x: usize) -> Self {
+                ((byte as $ty) << (Self::WIDTH - 8)).rotate_right((x % Self::WIDTH) as u32)
+            }
+
+            fn sprite_clipped(byte: u8, x: usize) -> Self {
+                ((byte as $ty) << (Self::WIDTH - 8)) >> (x % Self::WIDTH)
+            }
+
+            fn pixel(self, x: usize) -> bool {
+                (self >> (Self::WIDTH - 1 - x)) & 1 == 1
+            }
+        }
+    };
+}
+
+impl_row!(u64);
+impl_row!(u128);
+
+/// Monochrome framebuffer with one packed integer per row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Framebuffer<R: Row, const HEIGHT: usize> {
+    rows: [R; HEIGHT],
+}
+
+/// 64x32 framebuffer used by the original CHIP-8
+pub type Vram = Framebuffer<u64, 32>;
+/// 128x64 framebuffer used by the high resolution modes
+pub type HiresVram = Framebuffer<u128, 64>;
+
+impl<R: Row, const HEIGHT: usize> Framebuffer<R, HEIGHT> {
+    pub const WIDTH: usize = R::WIDTH;
+    pub const HEIGHT: usize = HEIGHT;
+
+    pub fn new() -> Self {
+        Self {
+            rows: [R::default(); HEIGHT],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        R::WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        HEIGHT
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [R::default(); HEIGHT];
+    }
+
+    pub fn rows(&self) -> &[R; HEIGHT] {
+        &self.rows
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y].pixel(x)
+    }
+
+    /// OR another framebuffer onto this one
+    pub fn merge(&mut self, other: &Self) {
+        for (row, other) in self.rows.iter_mut().zip(other.rows.iter()) {
+            *row = *row | *other;
+        }
+    }
+
+    /// XOR a sprite onto the framebuffer, one shifted row at a time. Sprites wrap
+    /// around both edges of the screen.
+    ///
+    /// Returns true if any lit pixel got turned off (collision)
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = R::default();
+
+        for (offset, byte) in sprite.iter().enumerate() {
+            let row = &mut self.rows[(y + offset) % HEIGHT];
+            let bits = R::sprite(*byte, x);
+
+            collision = collision | (*row & bits);
+            *row = *row ^ bits;
+        }
+
+        !collision.is_empty()
+    }
+
+    /// Like [`Framebuffer::draw_sprite`], except only the starting position wraps.
+    /// The parts of the sprite past the right and bottom edges are clipped.
+    pub fn draw_sprite_clipped(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = R::default();
+        let y = y % HEIGHT;
+
+        for (row, byte) in self.rows[y..].iter_mut().zip(sprite) {
+            let bits = R::sprite_clipped(*byte, x);
+
+            collision = collision | (*row & bits);
+            *row = *row ^ bits;
+        }
+
+        !collision.is_empty()
+    }
+}
+
+impl<R: Row, const HEIGHT: usize> Default for Framebuffer<R, HEIGHT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vram::{HiresVram, Vram};
+
+    #[test]
+    fn draw_sprite_sets_pixels() {
+        let mut vram = Vram::new();
+        let collision = vram.draw_sprite(2, 1, &[0b1010_0000]);
+
+        assert!(!collision);
+        assert!(vram.pixel(2, 1));
+        assert!(!vram.pixel(3, 1));
+        assert!(vram.pixel(4, 1));
+        assert_eq!(vram.rows()[1], 0b1010 << 58);
+    }
+
+    #[test]
+    fn draw_sprite_detects_collision() {
+        let mut vram = Vram::new();
+        vram.draw_sprite(0, 0, &[0xF0]);
+        let collision = vram.draw_sprite(3, 0, &[0x80]);
+
+        assert!(collision);
+        assert!(!vram.pixel(3, 0));
+    }
+
+    #[test]
+    fn draw_sprite_wraps() {
+        let mut vram = Vram::new();
+        vram.draw_sprite(62, 31, &[0xF0, 0x80]);
+
+        assert!(vram.pixel(62, 31));
+        assert!(vram.pixel(63, 31));
+        assert!(vram.pixel(0, 31));
+        assert!(vram.pixel(1, 31));
+        assert!(vram.pixel(62, 0));
+    }
+
+    #[test]
+    fn draw_sprite_clipped_stops_at_the_edges() {
+        let mut vram = Vram::new();
+        vram.draw_sprite_clipped(62 + 64, 31, &[0xF0, 0x80]);
+
+        assert!(vram.pixel(62, 31));
+        assert!(vram.pixel(63, 31));
+        assert!(!vram.pixel(0, 31));
+        assert!(!vram.pixel(62, 0));
+    }
+
+    #[test]
+    fn hires_rows_are_128_wide() {
+        let mut vram = HiresVram::new();
+        vram.draw_sprite(127, 63, &[0xC0]);
+
+        assert_eq!(vram.width(), 128);
+        assert!(vram.pixel(127, 63));
+        assert!(vram.pixel(0, 63));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::platform::Audio;
+use crate::sound::{Tone, ToneGenerator};
+
+pub const RECORDING_SAMPLE_RATE: u32 = 44100;
+// The emulator runs at a fixed 60 frames per second
+const FRAMES_PER_SECOND: u32 = 60;
+
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Writes mono 16 bit PCM samples to a WAV file. The sizes in the header are
+/// filled in by [`WavWriter::finish`].
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    sample_rate: u32,
+    samples: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(out: W, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = Self {
+            out,
+            sample_rate,
+            samples: 0,
+        };
+        writer.write_header()?;
+
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let block_align = BITS_PER_SAMPLE / 8;
+        let data_size = self.samples * block_align as u32;
+
+        self.out.write_all(b"RIFF")?;
+        self.out
+            .write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        self.out.write_all(b"WAVE")?;
+
+        self.out.write_all(b"fmt ")?;
+        self.out.write_all(&16u32.to_le_bytes())?;
+        self.out.write_all(&1u16.to_le_bytes())?; // PCM
+        self.out.write_all(&1u16.to_le_bytes())?; // mono
+        self.out.write_all(&self.sample_rate.to_le_bytes())?;
+        self.out
+            .write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+        self.out.write_all(&block_align.to_le_bytes())?;
+        self.out.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        self.out.write_all(b"data")?;
+        self.out.write_all(&data_size.to_le_bytes())
+    }
+
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        self.out.write_all(&value.to_le_bytes())?;
+        self.samples += 1;
+
+        Ok(())
+    }
+
+    /// Patch the header with the final sizes and flush
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()
+    }
+
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.finish()?;
+        Ok(self.out)
+    }
+}
+
+/// Records the beeper to a WAV file while passing everything through to another
+/// audio peripheral.
+///
+/// Samples are rendered per emulated frame rather than against wall-clock time,
+/// so the recording lines up exactly with the frames, and works in headless runs.
+pub struct AudioRecorder<W: Write + Seek = BufWriter<File>> {
+    inner: Box<dyn Audio>,
+    generator: ToneGenerator,
+    writer: WavWriter<W>,
+    // fractional samples carried over between frames
+    remainder: u32,
+}
+
+impl AudioRecorder {
+    pub fn create<P: AsRef<Path>>(path: P, tone: Tone, inner: Box<dyn Audio>) -> io::Result<Self> {
+        let writer = WavWriter::create(path, RECORDING_SAMPLE_RATE)?;
+        Ok(Self::new(writer, tone, inner))
+    }
+}
+
+impl<W: Write + Seek> AudioRecorder<W> {
+    pub fn new(writer: WavWriter<W>, tone: Tone, inner: Box<dyn Audio>) -> Self {
+        Self {
+            inner,
+            generator: ToneGenerator::new(tone, writer.sample_rate),
+            writer,
+            remainder: 0,
+        }
+    }
+
+    fn samples_for_frame(&mut self) -> u32 {
+        let total = self.writer.sample_rate + self.remainder;
+        self.remainder = total % FRAMES_PER_SECOND;
+        total / FRAMES_PER_SECOND
+    }
+}
+
+impl<W: Write + Seek> Audio for AudioRecorder<W> {
+    fn start_beep(&mut self) {
+        self.generator.set_gate(true);
+        self.inner.start_beep();
+    }
+
+    fn stop_beep(&mut self) {
+        self.generator.set_gate(false);
+        self.inner.stop_beep();
+    }
+
+    fn end_frame(&mut self) {
+        for _ in 0..self.samples_for_frame() {
+            let sample = self.generator.next_sample();
+            if let Err(e) = self.writer.write_sample(sample) {
+                eprintln!("failed to record audio: {}", e);
+                break;
+            }
+        }
+
+        self.inner.end_frame();
+    }
+}
+
+impl<W: Write + Seek> Drop for AudioRecorder<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.writer.finish() {
+            eprintln!("failed to finish audio recording: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::platform::Audio;
+    use crate::sound::Tone;
+    use crate::wav::{AudioRecorder, WavWriter};
+    use crate::Headless;
+
+    #[test]
+    fn records_a_fixed_amount_of_samples_per_frame() {
+        let writer = WavWriter::new(Cursor::new(Vec::new()), 44100).unwrap();
+        let mut recorder =
+            AudioRecorder::new(writer, Tone::default(), Box::new(Headless::new(None)));
+
+        recorder.start_beep();
+        recorder.end_frame();
+        recorder.end_frame();
+        recorder.stop_beep();
+        recorder.end_frame();
+
+        assert_eq!(recorder.writer.samples, 3 * 735);
+    }
+
+    #[test]
+    fn carries_fractional_samples_between_frames() {
+        let writer = WavWriter::new(Cursor::new(Vec::new()), 8000).unwrap();
+        let mut recorder =
+            AudioRecorder::new(writer, Tone::default(), Box::new(Headless::new(None)));
+
+        for _ in 0..60 {
+            recorder.end_frame();
+        }
+
+        assert_eq!(recorder.writer.samples, 8000);
+    }
+
+    #[test]
+    fn header_has_final_sizes() {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), 8000).unwrap();
+        for _ in 0..10 {
+            writer.write_sample(0.5).unwrap();
+        }
+
+        let bytes = writer.into_inner().unwrap().into_inner();
+        assert_eq!(bytes.len(), 44 + 20);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 36 + 20);
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 8000);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 20);
+        assert_eq!(
+            i16::from_le_bytes(bytes[44..46].try_into().unwrap()),
+            i16::MAX / 2
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    fs, io,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use chipmunk_backend::{
+    Cheat, Display, Filter, HostCommand, HostControl, Machine, Search, Target, Vram,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::database::sha1;
+
+const CHEATS_FILE: &str = "cheats.toml";
+// candidates listed in full after narrowing a search down
+const SHOWN_CANDIDATES: usize = 20;
+
+const HELP: &str = "\
+new              start a search from every byte of memory and the registers
+eq XX            keep the bytes that are now XX (hex)
+changed, same    keep the bytes that changed, or didn't, since the last step
+up, down         keep the bytes that went up, or down
+list             show what is left
+freeze M2F0=05   keep a byte at a value, saved for this rom
+freeze M2F0      keep a byte at its value now
+unfreeze M2F0    stop keeping a byte
+cheats           show the cheats in use";
+
+/// Cheats kept per rom, by SHA-1, in `chipmunk/cheats.toml`:
+///
+/// ```toml
+/// [roms]
+/// 0df2789f661358d8f7370e6cf93490c5bcd44b01 = ["M2F0=05", "VA=03"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheatFile {
+    roms: BTreeMap<String, Vec<String>>,
+}
+
+impl CheatFile {
+    pub fn path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join(CHEATS_FILE))
+    }
+
+    /// Load the cheats file, empty if it is missing or invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("ignoring invalid cheats {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config directory on this platform",
+            ));
+        };
+
+        let contents = toml::to_string_pretty(self).map_err(io::Error::other)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+
+    /// The cheats for the rom with this hash, leaving out invalid codes
+    pub fn cheats(&self, sha1: &str) -> Vec<Cheat> {
+        let codes = self.roms.get(sha1).map(Vec::as_slice).unwrap_or_default();
+        codes
+            .iter()
+            .filter_map(|code| match code.parse() {
+                Ok(cheat) => Some(cheat),
+                Err(e) => {
+                    eprintln!("ignoring cheat: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn set(&mut self, sha1: &str, cheats: &[Cheat]) {
+        if cheats.is_empty() {
+            self.roms.remove(sha1);
+        } else {
+            let codes = cheats.iter().map(Cheat::to_string).collect();
+            self.roms.insert(sha1.to_string(), codes);
+        }
+    }
+}
+
+/// Searching and freezing, one typed command at a time
+#[derive(Debug, Default)]
+pub struct Session {
+    search: Option<Search>,
+    pub cheats: Vec<Cheat>,
+}
+
+impl Session {
+    fn list(&self, out: &mut String) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let candidates = search.candidates();
+        let _ = writeln!(out, "{} left", candidates.len());
+        if candidates.len() <= SHOWN_CANDIDATES {
+            for (target, value) in candidates {
+                let _ = writeln!(out, "  {:<5}{:02X}", target.to_string(), value);
+            }
+        }
+    }
+
+    fn freeze(&mut self, code: &str, machine: &Machine) -> Result<(), String> {
+        let cheat = match code.contains('=') {
+            true => code.parse()?,
+            false => {
+                let target: Target = code.parse()?;
+                Cheat {
+                    target,
+                    value: target.read(machine),
+                }
+            }
+        };
+        self.cheats.retain(|frozen| frozen.target != cheat.target);
+        self.cheats.push(cheat);
+        Ok(())
+    }
+
+    /// Run a command against the machine as it is now. Returns what to print,
+    /// and whether the cheats changed.
+    pub fn execute(&mut self, line: &str, machine: &Machine) -> (String, bool) {
+        let mut out = String::new();
+        let mut words = line.split_whitespace();
+        let (command, argument) = (words.next().unwrap_or_default(), words.next());
+
+        let filter = match (command, argument) {
+            ("eq", Some(value)) => match u8::from_str_radix(value, 16) {
+                Ok(value) => Some(Filter::Equal(value)),
+                Err(_) => return (format!("expected a hex byte, got '{}'\n", value), false),
+            },
+            ("changed", _) => Some(Filter::Changed),
+            ("same", _) => Some(Filter::Unchanged),
+            ("up", _) => Some(Filter::Increased),
+            ("down", _) => Some(Filter::Decreased),
+            _ => None,
+        };
+        if let Some(filter) = filter {
+            match &mut self.search {
+                Some(search) => {
+                    search.narrow(machine, filter);
+                    self.list(&mut out);
+                }
+                None => out.push_str("no search yet, start one with 'new'\n"),
+            }
+            return (out, false);
+        }
+
+        match (command, argument) {
+            ("new", _) => {
+                let search = Search::new(machine);
+                let _ = writeln!(out, "{} bytes to search", search.candidates().len());
+                self.search = Some(search);
+            }
+            ("list", _) => self.list(&mut out),
+            ("freeze", Some(code)) => match self.freeze(code, machine) {
+                Ok(()) => return ("frozen\n".to_string(), true),
+                Err(e) => out = format!("{}\n", e),
+            },
+            ("unfreeze", Some(target)) => match target.parse::<Target>() {
+                Ok(target) => {
+                    self.cheats.retain(|cheat| cheat.target != target);
+                    return ("unfrozen\n".to_string(), true);
+                }
+                Err(e) => out = format!("{}\n", e),
+            },
+            ("cheats", _) => {
+                for cheat in &self.cheats {
+                    let _ = writeln!(out, "  {}", cheat);
+                }
+            }
+            ("", _) => {}
+            _ => {
+                out.push_str(HELP);
+                out.push('\n');
+            }
+        }
+        (out, false)
+    }
+}
+
+/// Reads cheat commands typed into the terminal while a rom runs. Wraps the
+/// display, to see the machine every frame, and host control, to send the
+/// cheats to the emulator.
+pub struct CheatConsole {
+    display: Box<dyn Display>,
+    host: Box<dyn HostControl>,
+    lines: Receiver<String>,
+    machine: Option<Machine>,
+    session: Session,
+    sha1: String,
+    commands: VecDeque<HostCommand>,
+}
+
+impl CheatConsole {
+    pub fn new(
+        display: Box<dyn Display>,
+        host: Box<dyn HostControl>,
+        sha1: String,
+        cheats: Vec<Cheat>,
+    ) -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        println!("cheats: type commands here, 'help' lists them");
+
+        Self {
+            display,
+            host,
+            lines,
+            machine: None,
+            session: Session {
+                search: None,
+                cheats,
+            },
+            sha1,
+            commands: VecDeque::new(),
+        }
+    }
+
+    fn execute(&mut self, line: &str) {
+        let Some(machine) = &self.machine else {
+            return;
+        };
+
+        let (out, changed) = self.session.execute(line, machine);
+        print!("{}", out);
+        if !changed {
+            return;
+        }
+
+        self.commands
+            .push_back(HostCommand::SetCheats(self.session.cheats.clone()));
+        let mut file = CheatFile::load();
+        file.set(&self.sha1, &self.session.cheats);
+        if let Err(e) = file.save() {
+            eprintln!("failed to save cheats: {}", e);
+        }
+    }
+
+    /// Start over with the cheats saved for a rom being loaded
+    fn switch_rom(&mut self, rom: &[u8]) {
+        self.sha1 = sha1(rom);
+        self.machine = None;
+        self.session = Session {
+            search: None,
+            cheats: CheatFile::load().cheats(&self.sha1),
+        };
+    }
+}
+
+impl Display for CheatConsole {
+    fn draw(&mut self, vram: &Vram) {
+        self.display.draw(vram);
+    }
+
+    fn inspect(&mut self, machine: &Machine) {
+        match &mut self.machine {
+            Some(copy) => copy.clone_from(machine),
+            None => self.machine = Some(machine.clone()),
+        }
+        self.display.inspect(machine);
+    }
+}
+
+impl HostControl for CheatConsole {
+    fn should_quit(&mut self) -> bool {
+        self.host.should_quit()
+    }
+
+    fn next_command(&mut self) -> Option<HostCommand> {
+        while let Ok(line) = self.lines.try_recv() {
+            self.execute(&line);
+        }
+
+        let command = self
+            .host
+            .next_command()
+            .or_else(|| self.commands.pop_front());
+        if let Some(HostCommand::LoadRom { rom, .. }) = &command {
+            self.switch_rom(rom);
+        }
+        command
+    }
+
+    fn throttle(&self) -> bool {
+        self.host.throttle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chipmunk_backend::{Cheat, Emulator, Headless, Machine, Peripherals, Settings};
+
+    use crate::cheats::{CheatFile, Session};
+
+    fn machine(memory: &[(u16, u8)]) -> Machine {
+        let emulator = Emulator::new(
+            vec![0x12, 0x00],
+            Peripherals::from_platform(Headless::new(None)),
+            Settings::default(),
+            false,
+        )
+        .unwrap();
+        let mut machine = emulator.machine().clone();
+        for (address, value) in memory {
+            machine.poke(*address, *value).unwrap();
+        }
+        machine
+    }
+
+    #[test]
+    fn sessions_search_and_freeze() {
+        let mut session = Session::default();
+        session.execute("new", &machine(&[(0x300, 3)]));
+
+        let (out, _) = session.execute("down", &machine(&[(0x300, 2)]));
+        assert_eq!(out, "1 left\n  M300 02\n");
+
+        let (_, changed) = session.execute("freeze M300", &machine(&[(0x300, 2)]));
+        assert!(changed);
+        session.execute("freeze VA=09", &machine(&[]));
+        assert_eq!(session.cheats.len(), 2);
+        assert_eq!(session.cheats[0].to_string(), "M300=02");
+
+        let (_, changed) = session.execute("unfreeze m300", &machine(&[]));
+        assert!(changed);
+        assert_eq!(session.cheats, ["VA=09".parse::<Cheat>().unwrap()]);
+    }
+
+    #[test]
+    fn cheat_files_are_keyed_by_hash() {
+        let mut file = CheatFile::default();
+        file.set("abc", &["M2F0=05".parse().unwrap()]);
+
+        let saved = toml::to_string_pretty(&file).unwrap();
+        let loaded: CheatFile = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.cheats("abc")[0].to_string(), "M2F0=05");
+        assert!(loaded.cheats("def").is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::palette::{Palette, PaletteName, Rgb};
+
+const CONFIG_FILE: &str = "config.toml";
+const MAX_RECENT_ROMS: usize = 8;
+
+/// Frontend settings, persisted in the user's config directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Most recently run roms first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recent: Vec<PathBuf>,
+    pub display: DisplayConfig,
+    // the file there didn't parse, and saving over it would lose what was in it
+    #[serde(skip)]
+    unparsed: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub palette: PaletteName,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fg: Option<Rgb>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg: Option<Rgb>,
+}
+
+impl DisplayConfig {
+    pub fn palette(&self) -> Palette {
+        let mut palette = Palette::named(self.palette);
+        if let Some(fg) = self.fg {
+            palette = palette.with_foreground(fg);
+        }
+        if let Some(bg) = self.bg {
+            palette = palette.with_background(bg);
+        }
+
+        palette
+    }
+}
+
+impl Config {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chipmunk"))
+    }
+
+    fn path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join(CONFIG_FILE))
+    }
+
+    /// Load the config file, falling back to the defaults if it is missing or
+    /// invalid. An invalid one is reported, and left alone by [`Config::save`].
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents, &path),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn parse(contents: &str, path: &Path) -> Self {
+        toml::from_str(contents).unwrap_or_else(|e| {
+            eprintln!(
+                "ignoring invalid config {}, and not saving over it: {}",
+                path.display(),
+                e
+            );
+            Self {
+                unparsed: true,
+                ..Self::default()
+            }
+        })
+    }
+
+    /// Move `rom` to the front of the recent roms
+    pub fn remember_rom(&mut self, rom: &Path) {
+        let rom = rom.canonicalize().unwrap_or_else(|_| rom.to_path_buf());
+
+        self.recent.retain(|recent| *recent != rom);
+        self.recent.insert(0, rom);
+        self.recent.truncate(MAX_RECENT_ROMS);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if self.unparsed {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the config file has errors, fix them first",
+            ));
+        }
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config directory on this platform",
+            ));
+        };
+
+        let contents = toml::to_string_pretty(self).map_err(io::Error::other)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::config::Config;
+
+    #[test]
+    fn recent_roms_are_kept_once_newest_first() {
+        let mut config = Config::default();
+        for rom in ["a.ch8", "b.ch8", "a.ch8"] {
+            config.remember_rom(Path::new(rom));
+        }
+        assert_eq!(
+            config.recent,
+            vec![PathBuf::from("a.ch8"), PathBuf::from("b.ch8")]
+        );
+
+        for n in 0..20 {
+            config.remember_rom(Path::new(&format!("{}.ch8", n)));
+        }
+        assert_eq!(config.recent.len(), 8);
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        let loaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.recent, config.recent);
+    }
+
+    #[test]
+    fn invalid_configs_are_not_saved_over() {
+        let mut config = Config::parse("[display\npalette = 3", Path::new("config.toml"));
+        config.remember_rom(Path::new("a.ch8"));
+
+        assert_eq!(
+            config.save().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+}
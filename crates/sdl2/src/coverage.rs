@@ -0,0 +1,156 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use chipmunk_backend::{Access, Coverage, MEMORY_SIZE, ROM_START};
+use serde_json::json;
+
+use crate::database::sha1;
+use crate::palette::Rgb;
+
+// The memory map shows 64 addresses per row, each as a square of pixels
+const COLUMNS: usize = 64;
+const CELL: usize = 8;
+
+const UNTOUCHED: Rgb = Rgb::hex(0x101010);
+// part of the rom, but never run, read or written: dead code or unreached data
+const UNUSED_ROM: Rgb = Rgb::hex(0x505050);
+const EXECUTED: Rgb = Rgb::hex(0x33CC33);
+const READ: Rgb = Rgb::hex(0x3377FF);
+const WRITTEN: Rgb = Rgb::hex(0xDD3333);
+const READ_AND_WRITTEN: Rgb = Rgb::hex(0xCC44CC);
+// code that gets written to
+const SELF_MODIFIED: Rgb = Rgb::hex(0xFFCC00);
+
+/// The coverage as JSON: runs of addresses per kind of access, with both ends
+/// included
+pub fn to_json(coverage: &Coverage, rom: &[u8]) -> String {
+    let mut report = json!({
+        "rom": {
+            "sha1": sha1(rom),
+            "start": ROM_START,
+            "size": rom.len(),
+        },
+    });
+    for access in Access::ALL {
+        let ranges: Vec<[u16; 2]> = coverage
+            .ranges(access)
+            .into_iter()
+            .map(|range| [*range.start(), *range.end()])
+            .collect();
+        report[access.name()] = json!(ranges);
+    }
+
+    serde_json::to_string_pretty(&report).expect("coverage is always valid JSON")
+}
+
+fn color(coverage: &Coverage, address: u16, rom_len: usize) -> Rgb {
+    let executed = coverage.get(address, Access::Execute);
+    let read = coverage.get(address, Access::Read);
+    let written = coverage.get(address, Access::Write);
+    let in_rom = (ROM_START as usize..ROM_START as usize + rom_len).contains(&(address as usize));
+
+    match (executed, read, written) {
+        (true, _, true) => SELF_MODIFIED,
+        (true, _, false) => EXECUTED,
+        (false, true, true) => READ_AND_WRITTEN,
+        (false, true, false) => READ,
+        (false, false, true) => WRITTEN,
+        (false, false, false) if in_rom => UNUSED_ROM,
+        (false, false, false) => UNTOUCHED,
+    }
+}
+
+/// RGB pixels of the memory map, `COLUMNS * CELL` wide
+fn memory_map(coverage: &Coverage, rom_len: usize) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(MEMORY_SIZE * CELL * CELL * 3);
+    for row in 0..MEMORY_SIZE / COLUMNS {
+        let line: Vec<u8> = (0..COLUMNS)
+            .flat_map(|column| {
+                let address = (row * COLUMNS + column) as u16;
+                color(coverage, address, rom_len).bytes().repeat(CELL)
+            })
+            .collect();
+        for _ in 0..CELL {
+            pixels.extend_from_slice(&line);
+        }
+    }
+    pixels
+}
+
+/// Write the coverage to `path` as JSON, and as a memory map next to it, with
+/// the extension changed to png. Returns where the map went.
+pub fn save(path: &Path, coverage: &Coverage, rom: &[u8]) -> io::Result<PathBuf> {
+    fs::write(path, to_json(coverage, rom))?;
+
+    let png_path = path.with_extension("png");
+    let size = (COLUMNS * CELL) as u32;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(&png_path)?), size, size);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&memory_map(coverage, rom.len())))
+        .map_err(io::Error::other)?;
+
+    Ok(png_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use chipmunk_backend::{Coverage, Emulator, Headless, Peripherals, Settings};
+    use serde_json::{json, Value};
+
+    use crate::coverage::{
+        color, memory_map, to_json, CELL, COLUMNS, EXECUTED, UNTOUCHED, UNUSED_ROM,
+    };
+    use crate::palette::Rgb;
+
+    fn run(rom: &[u8], cycles: usize) -> Coverage {
+        let coverage = Rc::new(RefCell::new(Coverage::new()));
+        let mut emulator = Emulator::new(
+            rom.to_vec(),
+            Peripherals::from_platform(Headless::new(None)),
+            Settings::default(),
+            false,
+        )
+        .unwrap();
+        emulator.add_observer(Box::new(Rc::clone(&coverage)));
+        for _ in 0..cycles {
+            emulator.step().unwrap();
+        }
+
+        let coverage = coverage.borrow().clone();
+        coverage
+    }
+
+    #[test]
+    fn exports_ranges_and_a_memory_map() {
+        // spin, then a word that is never reached
+        let rom = [0x12, 0x00, 0x00, 0xE0];
+        let coverage = run(&rom, 3);
+
+        let json: Value = serde_json::from_str(&to_json(&coverage, &rom)).unwrap();
+        assert_eq!(json["executed"], json!([[0x200, 0x200]]));
+        assert_eq!(json["written"], json!([]));
+        assert_eq!(json["rom"]["size"], 4);
+
+        assert_eq!(color(&coverage, 0x200, rom.len()), EXECUTED);
+        assert_eq!(color(&coverage, 0x202, rom.len()), UNUSED_ROM);
+        assert_eq!(color(&coverage, 0x204, rom.len()), UNTOUCHED);
+
+        let pixels = memory_map(&coverage, rom.len());
+        let width = COLUMNS * CELL;
+        assert_eq!(pixels.len(), width * width * 3);
+        // 0x200 is the first cell of row 8
+        let offset = 8 * CELL * width * 3;
+        assert_eq!(
+            Rgb(pixels[offset], pixels[offset + 1], pixels[offset + 2]),
+            EXECUTED
+        );
+    }
+}
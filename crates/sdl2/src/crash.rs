@@ -0,0 +1,116 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chipmunk_backend::{Crash, Vram};
+
+use crate::palette::Palette;
+
+// Each pixel of the screen as a square this big
+const SCALE: usize = 8;
+
+/// RGB pixels of the screen, `SCALE` times as big
+fn screen(vram: &Vram, palette: &Palette) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(vram.width() * vram.height() * SCALE * SCALE * 3);
+    for y in 0..vram.height() {
+        let line: Vec<u8> = (0..vram.width())
+            .flat_map(|x| {
+                let pixel = vram.pixel(x, y) as u8;
+                palette.color(pixel).bytes().repeat(SCALE)
+            })
+            .collect();
+        for _ in 0..SCALE {
+            pixels.extend_from_slice(&line);
+        }
+    }
+    pixels
+}
+
+fn write_report(dir: &Path, crash: &Crash, palette: &Palette) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("report.txt"), crash.describe())?;
+    fs::write(dir.join("settings.txt"), crash.describe_settings())?;
+    fs::write(dir.join("inputs.txt"), crash.describe_inputs())?;
+    fs::write(dir.join("memory.bin"), crash.machine.memory())?;
+    fs::write(dir.join("rom.ch8"), &crash.rom)?;
+
+    let vram = crash.machine.framebuffer();
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(dir.join("screen.png"))?),
+        (vram.width() * SCALE) as u32,
+        (vram.height() * SCALE) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&screen(vram, palette)))
+        .map_err(io::Error::other)
+}
+
+/// Write what led up to `crash` to a new directory in the current one, for
+/// attaching to a bug report. Returns the directory.
+pub fn save(crash: &Crash, palette: &Palette) -> io::Result<PathBuf> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+    let dir = PathBuf::from(format!("chipmunk-crash-{}", millis));
+
+    write_report(&dir, crash, palette)?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, rc::Rc};
+
+    use chipmunk_backend::{Emulator, Headless, Peripherals, Settings};
+
+    use crate::crash::{write_report, SCALE};
+    use crate::palette::{Palette, PaletteName};
+
+    #[test]
+    fn reports_have_everything_to_run_into_the_crash_again() {
+        // draw the font's 0, then store V0 at 0x100, which strict mode stops at
+        let rom = vec![0xD0, 0x05, 0xA1, 0x00, 0xF0, 0x55];
+        let mut emulator = Emulator::new(
+            rom.clone(),
+            Peripherals::from_platform(Headless::new(Some(3))),
+            Settings::default(),
+            false,
+        )
+        .unwrap();
+        emulator.set_strict(true);
+        emulator.machine_mut().set_i(0x050).unwrap();
+        let dir = std::env::temp_dir().join(format!("chipmunk-crash-test-{}", std::process::id()));
+        let target = dir.clone();
+        let written = Rc::new(RefCell::new(None));
+        let result = Rc::clone(&written);
+        emulator.set_crash_handler(Box::new(move |crash| {
+            let palette = Palette::named(PaletteName::Classic);
+            *result.borrow_mut() = Some(write_report(&target, crash, &palette));
+        }));
+
+        emulator.start();
+
+        written.borrow_mut().take().unwrap().unwrap();
+        let report = fs::read_to_string(dir.join("report.txt")).unwrap();
+        assert!(report.contains("  204: F055 LD [I], V0\n"));
+        assert_eq!(fs::read(dir.join("rom.ch8")).unwrap(), rom);
+        assert_eq!(fs::read(dir.join("memory.bin")).unwrap().len(), 4096);
+        assert_eq!(
+            fs::read_to_string(dir.join("inputs.txt")).unwrap(),
+            "1 0000\n"
+        );
+        let screen = png::Decoder::new(fs::File::open(dir.join("screen.png")).unwrap())
+            .read_info()
+            .unwrap();
+        assert_eq!(screen.info().width as usize, 64 * SCALE);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
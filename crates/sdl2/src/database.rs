@@ -0,0 +1,381 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use chipmunk_backend::{Quirks, Settings, Stack, Variant, DEFAULT_TICKRATE};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::config::Config;
+use crate::palette::Rgb;
+
+// A checkout (or the release files) of the community chip-8-database
+const DATABASE_DIR: &str = "chip-8-database";
+const HASHES_FILE: &str = "sha1-hashes.json";
+const PROGRAMS_FILE: &str = "programs.json";
+const PLATFORMS_FILE: &str = "platforms.json";
+// Local corrections, keyed by SHA-1 like the database
+const OVERRIDES_FILE: &str = "rom-overrides.json";
+
+/// Direction and button names from the database (like "up" or "a") to the
+/// CHIP-8 key the rom uses for them
+pub type KeyHints = BTreeMap<String, u8>;
+
+/// Quirks that differ from the platform's, in the database's naming
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    fn apply(&self, quirks: &mut Quirks) {
+        let fields = [
+            (&mut quirks.shift, self.shift),
+            (
+                &mut quirks.memory_increment_by_x,
+                self.memory_increment_by_x,
+            ),
+            (
+                &mut quirks.memory_leave_i_unchanged,
+                self.memory_leave_i_unchanged,
+            ),
+            (&mut quirks.wrap, self.wrap),
+            (&mut quirks.jump, self.jump),
+            (&mut quirks.vblank, self.vblank),
+            (&mut quirks.logic, self.logic),
+        ];
+        for (quirk, value) in fields {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Colors {
+    pixels: Vec<Rgb>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RomRecord {
+    /// Platform ids the rom runs on, best first
+    platforms: Vec<String>,
+    quirky_platforms: BTreeMap<String, QuirkOverrides>,
+    tickrate: Option<usize>,
+    keys: KeyHints,
+    colors: Option<Colors>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Program {
+    title: String,
+    authors: Vec<String>,
+    roms: BTreeMap<String, RomRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    default_tickrate: Option<usize>,
+    #[serde(default)]
+    quirks: QuirkOverrides,
+}
+
+/// One entry of `rom-overrides.json`. Every field is optional, and the ones set
+/// win over the database.
+///
+/// ```json
+/// {
+///   "0df2789f661358d8f7370e6cf93490c5bcd44b01": {
+///     "title": "Pong",
+///     "platform": "originalChip8",
+///     "tickrate": 15,
+///     "quirks": { "vblank": false },
+///     "keys": { "up": 1, "down": 4 },
+///     "colors": { "pixels": ["#000000", "#33ff33"] }
+///   }
+/// }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Override {
+    title: Option<String>,
+    platform: Option<String>,
+    tickrate: Option<usize>,
+    quirks: QuirkOverrides,
+    keys: KeyHints,
+    colors: Option<Colors>,
+}
+
+/// The chip-8-database files, and the local overrides
+#[derive(Debug, Default)]
+pub struct Database {
+    hashes: BTreeMap<String, usize>,
+    programs: Vec<Program>,
+    platforms: Vec<Platform>,
+    overrides: BTreeMap<String, Override>,
+}
+
+/// What is known about a rom, and how it should be run
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomProfile {
+    pub sha1: String,
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub platform: Option<String>,
+    pub settings: Settings,
+    pub keys: KeyHints,
+    /// Background first, then the colors of the planes
+    pub colors: Vec<Rgb>,
+}
+
+impl RomProfile {
+    /// Window caption for the rom
+    pub fn caption(&self) -> String {
+        match &self.title {
+            Some(title) => format!("chipmunk - {}", title),
+            None => "chipmunk".to_string(),
+        }
+    }
+}
+
+pub fn sha1(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// The dialect a platform in the database runs, for the ones based on an
+/// interpreter chipmunk knows
+pub fn platform_variant(id: &str) -> Option<Variant> {
+    match id {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some(Variant::Chip8),
+        "superchip1" | "superchip" | "megachip8" => Some(Variant::SuperChip),
+        "xochip" => Some(Variant::XoChip),
+        _ => None,
+    }
+}
+
+/// The quirks of the platforms in the database, for when `platforms.json` is
+/// missing
+fn builtin_platform(id: &str) -> Option<Quirks> {
+    match id {
+        "modernChip8" => Some(Quirks::default()),
+        _ => platform_variant(id).map(Quirks::for_variant),
+    }
+}
+
+fn read_json<T: DeserializeOwned + Default>(path: PathBuf) -> T {
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("ignoring invalid {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+impl Database {
+    pub fn dir() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join(DATABASE_DIR))
+    }
+
+    pub fn overrides_path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join(OVERRIDES_FILE))
+    }
+
+    /// Load whatever of the database and the overrides there is. Missing or
+    /// invalid files are left out.
+    pub fn load() -> Self {
+        let mut database = Self::default();
+        if let Some(dir) = Self::dir() {
+            database.hashes = read_json(dir.join(HASHES_FILE));
+            database.programs = read_json(dir.join(PROGRAMS_FILE));
+            database.platforms = read_json(dir.join(PLATFORMS_FILE));
+        }
+        if let Some(path) = Self::overrides_path() {
+            database.overrides = read_json(path);
+        }
+
+        database
+    }
+
+    fn platform(&self, id: &str) -> Option<(Quirks, Option<usize>)> {
+        match self.platforms.iter().find(|platform| platform.id == id) {
+            Some(platform) => {
+                let mut quirks = Quirks::default();
+                platform.quirks.apply(&mut quirks);
+                Some((quirks, platform.default_tickrate))
+            }
+            None => builtin_platform(id).map(|quirks| (quirks, None)),
+        }
+    }
+
+    /// Look up a rom by its SHA-1. Unknown roms get the default settings.
+    pub fn profile(&self, sha1: &str) -> RomProfile {
+        let program = self
+            .hashes
+            .get(sha1)
+            .and_then(|index| self.programs.get(*index));
+        let record = program.and_then(|program| program.roms.get(sha1));
+        let local = self.overrides.get(sha1);
+
+        let platform = local
+            .and_then(|local| local.platform.clone())
+            .or_else(|| record.and_then(|record| record.platforms.first().cloned()));
+
+        let mut settings = Settings::default();
+        if let Some((quirks, tickrate)) = platform.as_deref().and_then(|id| self.platform(id)) {
+            settings.quirks = quirks;
+            settings.tickrate = tickrate.unwrap_or(DEFAULT_TICKRATE);
+        }
+        if let Some(variant) = platform.as_deref().and_then(platform_variant) {
+            settings.stack = Stack::for_variant(variant);
+        }
+
+        let mut keys = KeyHints::new();
+        let mut colors = Vec::new();
+        if let Some(record) = record {
+            if let Some(quirks) = platform
+                .as_ref()
+                .and_then(|id| record.quirky_platforms.get(id))
+            {
+                quirks.apply(&mut settings.quirks);
+            }
+            settings.tickrate = record.tickrate.unwrap_or(settings.tickrate);
+            keys = record.keys.clone();
+            if let Some(record_colors) = &record.colors {
+                colors = record_colors.pixels.clone();
+            }
+        }
+
+        let mut title = program.map(|program| program.title.clone());
+        if let Some(local) = local {
+            title = local.title.clone().or(title);
+            local.quirks.apply(&mut settings.quirks);
+            settings.tickrate = local.tickrate.unwrap_or(settings.tickrate);
+            keys.extend(local.keys.clone());
+            if let Some(local_colors) = &local.colors {
+                colors = local_colors.pixels.clone();
+            }
+        }
+
+        RomProfile {
+            sha1: sha1.to_string(),
+            title,
+            authors: program
+                .map(|program| program.authors.clone())
+                .unwrap_or_default(),
+            platform,
+            settings,
+            keys,
+            colors,
+        }
+    }
+
+    /// The profile of `rom`, from the files on disk
+    pub fn lookup(rom: &[u8]) -> RomProfile {
+        Self::load().profile(&sha1(rom))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chipmunk_backend::{Quirks, Settings, Stack, Variant, DEFAULT_TICKRATE};
+
+    use crate::database::{sha1, Database};
+    use crate::palette::Rgb;
+
+    const HASH: &str = "0df2789f661358d8f7370e6cf93490c5bcd44b01";
+
+    fn database(overrides: &str) -> Database {
+        Database {
+            hashes: serde_json::from_str(&format!("{{\"{}\": 0}}", HASH)).unwrap(),
+            programs: serde_json::from_str(&format!(
+                r##"[{{
+                    "title": "Pong",
+                    "authors": ["Paul Vervalin"],
+                    "roms": {{
+                        "{}": {{
+                            "platforms": ["superchip", "originalChip8"],
+                            "quirkyPlatforms": {{ "superchip": {{ "wrap": true }} }},
+                            "tickrate": 20,
+                            "keys": {{ "up": 1, "down": 4 }},
+                            "colors": {{ "pixels": ["#000000", "#ffffff"] }}
+                        }}
+                    }}
+                }}]"##,
+                HASH
+            ))
+            .unwrap(),
+            platforms: Vec::new(),
+            overrides: serde_json::from_str(overrides).unwrap(),
+        }
+    }
+
+    #[test]
+    fn hashes_roms_with_sha1() {
+        assert_eq!(sha1(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn known_roms_get_their_platform_settings() {
+        let profile = database("{}").profile(HASH);
+
+        assert_eq!(profile.title.as_deref(), Some("Pong"));
+        assert_eq!(profile.caption(), "chipmunk - Pong");
+        assert_eq!(profile.platform.as_deref(), Some("superchip"));
+        assert_eq!(profile.settings.tickrate, 20);
+        assert_eq!(
+            profile.settings.quirks,
+            Quirks {
+                wrap: true,
+                ..Quirks::for_variant(Variant::SuperChip)
+            }
+        );
+        assert_eq!(
+            profile.settings.stack,
+            Stack::for_variant(Variant::SuperChip)
+        );
+        assert_eq!(profile.keys.get("down"), Some(&4));
+        assert_eq!(profile.colors, [Rgb::hex(0x000000), Rgb::hex(0xFFFFFF)]);
+    }
+
+    #[test]
+    fn local_overrides_win_field_by_field() {
+        let overrides = format!(
+            r#"{{ "{}": {{ "title": "My Pong", "platform": "originalChip8",
+                "quirks": {{ "vblank": false }}, "keys": {{ "up": 2 }} }} }}"#,
+            HASH
+        );
+        let profile = database(&overrides).profile(HASH);
+
+        assert_eq!(profile.title.as_deref(), Some("My Pong"));
+        assert_eq!(profile.authors, ["Paul Vervalin"]);
+        // the quirks for superchip don't apply any more
+        assert!(!profile.settings.quirks.wrap);
+        assert!(!profile.settings.quirks.vblank);
+        assert!(profile.settings.quirks.logic);
+        assert_eq!(profile.settings.stack.depth, 12);
+        assert_eq!(profile.settings.tickrate, 20);
+        assert_eq!(profile.keys.get("up"), Some(&2));
+        assert_eq!(profile.keys.get("down"), Some(&4));
+    }
+
+    #[test]
+    fn unknown_roms_run_with_the_defaults() {
+        let profile = database("{}").profile(&sha1(&[0x12, 0x00]));
+
+        assert_eq!(profile.title, None);
+        assert_eq!(profile.caption(), "chipmunk");
+        assert_eq!(profile.settings, Settings::default());
+        assert_eq!(profile.settings.tickrate, DEFAULT_TICKRATE);
+    }
+}
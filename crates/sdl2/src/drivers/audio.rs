@@ -1,33 +1,55 @@
+use std::{cell::Cell, rc::Rc};
+
+use chipmunk_backend::{Audio, Tone, ToneGenerator};
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+const VOLUME_STEP: f32 = 0.01;
+
+/// Volume changes requested through the event pump, applied by the audio device
+pub struct VolumeControl {
+    volume: Cell<f32>,
+    changed: Cell<bool>,
+}
+
+impl VolumeControl {
+    pub fn new(volume: f32) -> Self {
+        Self {
+            volume: Cell::new(volume.clamp(0.0, 1.0)),
+            changed: Cell::new(false),
+        }
+    }
+
+    pub fn step(&self, up: bool) {
+        let step = if up { VOLUME_STEP } else { -VOLUME_STEP };
+        let volume = (self.volume.get() + step).clamp(0.0, 1.0);
+
+        self.volume.set(volume);
+        self.changed.set(true);
+        println!("volume: {:.2}", volume);
+    }
+
+    fn take_change(&self) -> Option<f32> {
+        self.changed.take().then(|| self.volume.get())
+    }
 }
 
-impl AudioCallback for SquareWave {
+struct Beeper(ToneGenerator);
+
+impl AudioCallback for Beeper {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        self.0.fill(out);
     }
 }
 
 pub struct Sdl2Audio {
-    device: AudioDevice<SquareWave>,
+    device: AudioDevice<Beeper>,
+    volume: Rc<VolumeControl>,
 }
 
 impl Sdl2Audio {
-    pub fn new(context: &mut sdl2::Sdl) -> Self {
+    pub fn new(context: &mut sdl2::Sdl, tone: Tone, volume: Rc<VolumeControl>) -> Self {
         let audio_subsystem = context.audio().unwrap();
 
         let desired_spec = AudioSpecDesired {
@@ -39,22 +61,35 @@ impl Sdl2Audio {
         let device = audio_subsystem
             .open_playback(None, &desired_spec, |spec| {
                 // initialize the audio callback
-                SquareWave {
-                    phase_inc: 440.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.05,
-                }
+                Beeper(ToneGenerator::new(tone, spec.freq as u32))
             })
             .unwrap();
 
-        Self { device }
+        // The device keeps running, the gate decides if anything is heard. Pausing
+        // the device instead would cut the release short.
+        device.resume();
+
+        Self { device, volume }
+    }
+
+    fn apply_volume(&mut self) {
+        if let Some(volume) = self.volume.take_change() {
+            self.device.lock().0.set_volume(volume);
+        }
+    }
+}
+
+impl Audio for Sdl2Audio {
+    fn start_beep(&mut self) {
+        self.apply_volume();
+        self.device.lock().0.set_gate(true);
     }
 
-    pub fn start(&mut self) {
-        self.device.resume();
+    fn stop_beep(&mut self) {
+        self.device.lock().0.set_gate(false);
     }
 
-    pub fn stop(&mut self) {
-        self.device.pause();
+    fn end_frame(&mut self) {
+        self.apply_volume();
     }
 }
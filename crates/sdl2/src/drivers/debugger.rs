@@ -0,0 +1,314 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use chipmunk_backend::{mnemonic, HostCommand, Machine, Reg, MEMORY_SIZE};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use super::menu::{draw_text, TEXT_SCALE};
+use crate::font::{GLYPH_HEIGHT, GLYPH_WIDTH, POINTER};
+use crate::palette::{Palette, Rgb};
+
+// The game keeps its 640x320 in the top left, the panels go around it
+pub const WIDTH: u32 = 1000;
+pub const HEIGHT: u32 = 540;
+pub const GAME: (u32, u32) = (640, 320);
+
+const MARGIN: i32 = 8;
+const CHAR_WIDTH: i32 = (GLYPH_WIDTH as i32 + 1) * TEXT_SCALE;
+const LINE_HEIGHT: i32 = (GLYPH_HEIGHT as i32 + 3) * TEXT_SCALE;
+const BOTTOM: i32 = GAME.1 as i32 + 12;
+const LINES: usize = 10;
+
+// Disassembly under the game on the left, with a few instructions before PC
+const BEFORE_PC: u16 = 4;
+
+// Memory as hex, next to the disassembly
+const HEX_X: i32 = 336;
+const BYTES_PER_ROW: u16 = 8;
+
+// Registers, stack and the sprite at I, right of the game
+const PANEL_X: i32 = GAME.0 as i32 + 20;
+const SPRITE_ROWS: u16 = 15;
+const SPRITE_CELL: u32 = 6;
+
+/// Panels with the registers, the stack, disassembly around PC with breakpoints,
+/// memory with editing and the sprite at I, drawn next to the game. Click an
+/// instruction to toggle a breakpoint on it. Click a byte and type two hex
+/// digits to change it, best done while paused since the keys still reach the
+/// keypad.
+pub struct Debugger {
+    machine: Option<Machine>,
+    breakpoints: BTreeSet<u16>,
+    // first address of the memory view
+    memory_start: u16,
+    // the byte being edited, and its high digit once typed
+    selected: Option<u16>,
+    typed: Option<u8>,
+    commands: VecDeque<HostCommand>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The line of a bottom panel starting at `left` under the cursor, and the
+/// column in it
+fn line_at(x: i32, y: i32, left: i32, columns: i32) -> Option<(usize, i32)> {
+    let row = (y - BOTTOM).div_euclid(LINE_HEIGHT);
+    let column = (x - left).div_euclid(CHAR_WIDTH);
+    ((0..LINES as i32).contains(&row) && (0..columns).contains(&column))
+        .then_some((row as usize, column))
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            machine: None,
+            breakpoints: BTreeSet::new(),
+            memory_start: 0x200,
+            selected: None,
+            typed: None,
+            commands: VecDeque::new(),
+        }
+    }
+
+    pub fn inspect(&mut self, machine: &Machine) {
+        match &mut self.machine {
+            Some(copy) => copy.clone_from(machine),
+            None => self.machine = Some(machine.clone()),
+        }
+    }
+
+    pub fn next_command(&mut self) -> Option<HostCommand> {
+        self.commands.pop_front()
+    }
+
+    /// Forget the breakpoints and the byte being edited, which were for the
+    /// rom before
+    pub fn rom_changed(&mut self) {
+        self.breakpoints.clear();
+        self.selected = None;
+        self.typed = None;
+    }
+
+    fn disassembly_start(&self) -> u16 {
+        let pc = self.machine.as_ref().map_or(0x200, |m| m.registers().pc());
+        pc.saturating_sub(BEFORE_PC * 2)
+    }
+
+    fn scroll(&mut self, rows: i32) {
+        let last = (MEMORY_SIZE as u16 / BYTES_PER_ROW - LINES as u16) * BYTES_PER_ROW;
+        let start = self.memory_start as i32 + rows * BYTES_PER_ROW as i32;
+        self.memory_start = start.clamp(0, last as i32) as u16;
+    }
+
+    fn click(&mut self, x: i32, y: i32) {
+        self.typed = None;
+        self.selected = None;
+
+        if let Some((row, _)) = line_at(x, y, MARGIN, HEX_X / CHAR_WIDTH) {
+            let address = self.disassembly_start() + 2 * row as u16;
+            if !self.breakpoints.remove(&address) {
+                self.breakpoints.insert(address);
+            }
+            self.commands
+                .push_back(HostCommand::ToggleBreakpoint(address));
+        } else if let Some((row, column)) = line_at(x, y, HEX_X, 4 + 3 * BYTES_PER_ROW as i32) {
+            // "ADR xx xx ..", the byte under the cursor if any
+            if column >= 4 {
+                let byte = (column - 4) / 3;
+                self.selected = Some(self.memory_start + row as u16 * BYTES_PER_ROW + byte as u16);
+            }
+        }
+    }
+
+    fn type_digit(&mut self, digit: u8) {
+        let Some(address) = self.selected else {
+            return;
+        };
+        let Some(high) = self.typed.take() else {
+            self.typed = Some(digit);
+            return;
+        };
+
+        let value = high << 4 | digit;
+        if let Some(machine) = &mut self.machine {
+            let _ = machine.poke(address, value);
+        }
+        self.commands
+            .push_back(HostCommand::Poke { address, value });
+        // on to the next byte, like a hex editor
+        self.selected = (address as usize + 1 < MEMORY_SIZE).then_some(address + 1);
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => self.click(x, y),
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Right,
+                ..
+            } => {
+                self.selected = None;
+                self.typed = None;
+            }
+            Event::MouseWheel { y, .. } => self.scroll(-y.signum()),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => {
+                let digit = match keycode.name().as_str() {
+                    name if name.len() == 1 => u8::from_str_radix(name, 16).ok(),
+                    _ => None,
+                };
+                if let (Some(digit), Some(_)) = (digit, self.selected) {
+                    self.type_digit(digit);
+                } else if keycode == Keycode::PageDown {
+                    self.scroll(LINES as i32);
+                } else if keycode == Keycode::PageUp {
+                    self.scroll(-(LINES as i32));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, palette: &Palette) {
+        let Some(machine) = &self.machine else {
+            return;
+        };
+        let color = |Rgb(r, g, b): Rgb| Color::RGB(r, g, b);
+        let (background, foreground) = (color(palette.background()), color(palette.color(1)));
+        let line_y = |row: usize| BOTTOM + row as i32 * LINE_HEIGHT;
+        let highlight = |canvas: &mut Canvas<Window>, x: i32, y: i32, chars: i32| {
+            canvas.set_draw_color(foreground);
+            let _ = canvas.fill_rect(Rect::new(
+                x - 2,
+                y - 2,
+                (chars * CHAR_WIDTH) as u32,
+                LINE_HEIGHT as u32,
+            ));
+        };
+
+        let registers = machine.registers();
+        let pc = registers.pc();
+
+        // disassembly
+        let start = self.disassembly_start();
+        for row in 0..LINES {
+            let address = start + 2 * row as u16;
+            if address as usize + 1 >= MEMORY_SIZE {
+                break;
+            }
+            let instruction = machine.instruction_at(address);
+            let text = format!(
+                "{}{}{:03X} {:04X} {}",
+                if address == pc { POINTER } else { ' ' },
+                if self.breakpoints.contains(&address) {
+                    '*'
+                } else {
+                    ' '
+                },
+                address,
+                instruction,
+                mnemonic(instruction)
+            );
+            let (x, y) = (MARGIN, line_y(row));
+            if address == pc {
+                highlight(canvas, x, y, text.len() as i32);
+                draw_text(canvas, &text, x, y, background);
+            } else {
+                draw_text(canvas, &text, x, y, foreground);
+            }
+        }
+
+        // memory
+        let memory = machine.memory();
+        for row in 0..LINES {
+            let address = self.memory_start + row as u16 * BYTES_PER_ROW;
+            let y = line_y(row);
+            draw_text(canvas, &format!("{:03X}", address), HEX_X, y, foreground);
+            for byte in 0..BYTES_PER_ROW {
+                let address = address + byte;
+                let x = HEX_X + (4 + 3 * byte as i32) * CHAR_WIDTH;
+                let text = match self.typed {
+                    Some(high) if self.selected == Some(address) => format!("{:X}_", high),
+                    _ => format!("{:02X}", memory[address as usize]),
+                };
+                if self.selected == Some(address) {
+                    highlight(canvas, x, y, 2);
+                    draw_text(canvas, &text, x, y, background);
+                } else {
+                    draw_text(canvas, &text, x, y, foreground);
+                }
+            }
+        }
+
+        // registers and stack
+        let mut panel = vec![
+            format!("PC {:03X}  I {:03X}", pc, registers.get_i()),
+            format!(
+                "DT {:02X}  ST {:02X}",
+                registers.get(Reg::DelayTimer),
+                registers.get(Reg::SoundTimer)
+            ),
+        ];
+        for first in (0..16).step_by(4) {
+            let line: Vec<String> = (first..first + 4)
+                .map(|index: usize| format!("V{:X} {:02X}", index, registers.get(index.into())))
+                .collect();
+            panel.push(line.join(" "));
+        }
+        panel.push(format!("STACK {}", machine.stack().len()));
+        for calls in machine.stack().chunks(4) {
+            let line: Vec<String> = calls.iter().map(|ret| format!("{:03X}", ret)).collect();
+            panel.push(line.join(" "));
+        }
+        panel.push("SPRITE AT I".to_string());
+        for (row, line) in panel.iter().enumerate() {
+            draw_text(
+                canvas,
+                line,
+                PANEL_X,
+                MARGIN + row as i32 * LINE_HEIGHT,
+                foreground,
+            );
+        }
+
+        // the sprite a DXYN would draw from I, as many rows as it could have
+        let top = MARGIN + panel.len() as i32 * LINE_HEIGHT;
+        let mut cells = Vec::new();
+        for row in 0..SPRITE_ROWS {
+            let byte = memory[registers.get_i().wrapping_add(row) as usize % MEMORY_SIZE];
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    cells.push(Rect::new(
+                        PANEL_X + bit * SPRITE_CELL as i32,
+                        top + row as i32 * SPRITE_CELL as i32,
+                        SPRITE_CELL,
+                        SPRITE_CELL,
+                    ));
+                }
+            }
+        }
+        canvas.set_draw_color(foreground);
+        let _ = canvas.fill_rects(&cells);
+        let _ = canvas.draw_rect(Rect::new(
+            PANEL_X - 1,
+            top - 1,
+            8 * SPRITE_CELL + 2,
+            SPRITE_ROWS as u32 * SPRITE_CELL + 2,
+        ));
+    }
+}
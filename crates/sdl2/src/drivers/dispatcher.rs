@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use chipmunk_backend::{detect_quirks, HostCommand, Stack, STATE_SLOTS};
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Scancode},
+};
+
+use super::{audio::VolumeControl, display::WindowRequests};
+use crate::cheats::CheatFile;
+use crate::config::Config;
+use crate::database::{Database, RomProfile};
+use crate::hotkeys::Action;
+use crate::info;
+use crate::keymap::{Keymap, Layout};
+use crate::library;
+
+/// Host key to hotkey action, matched the same way as the keypad
+enum Actions {
+    Physical(HashMap<Scancode, Action>),
+    Logical(HashMap<Keycode, Action>),
+}
+
+impl Actions {
+    fn new(keymap: &Keymap) -> Self {
+        let keys = keymap.hotkey_keys();
+        let unknown = |action: Action, name: &str| {
+            eprintln!("unknown key '{}' for hotkey {}", name, action.name());
+        };
+
+        match keymap.layout {
+            Layout::Physical => Actions::Physical(
+                keys.into_iter()
+                    .filter_map(|(action, name)| match Scancode::from_name(name) {
+                        Some(scancode) => Some((scancode, action)),
+                        None => {
+                            unknown(action, name);
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+            Layout::Logical => Actions::Logical(
+                keys.into_iter()
+                    .filter_map(|(action, name)| match Keycode::from_name(name) {
+                        Some(keycode) => Some((keycode, action)),
+                        None => {
+                            unknown(action, name);
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn get(&self, scancode: Option<Scancode>, keycode: Option<Keycode>) -> Option<Action> {
+        match self {
+            Actions::Physical(map) => scancode.and_then(|scancode| map.get(&scancode).copied()),
+            Actions::Logical(map) => keycode.and_then(|keycode| map.get(&keycode).copied()),
+        }
+    }
+}
+
+/// Read a rom, if it can be and it fits in memory
+fn read_rom(path: &Path) -> Option<Vec<u8>> {
+    library::read_rom(path)
+        .map_err(|e| eprintln!("failed to open {}: {}", path.display(), e))
+        .ok()
+}
+
+/// Turns hotkeys and window events into host commands and requests. It only
+/// looks at events, so the keypad still sees every key.
+pub struct Dispatcher {
+    actions: Actions,
+    commands: VecDeque<HostCommand>,
+    quit: bool,
+    slot: usize,
+    // read again on reload, so a rebuilt rom can be tried without restarting
+    rom: PathBuf,
+    window_requests: Rc<WindowRequests>,
+    volume: Rc<VolumeControl>,
+    // the keymap of a rom switched to, for the keyboard and gamepads to pick up
+    keymap: Option<Keymap>,
+    // guess the quirks of roms loaded from here instead of using the database's
+    auto_quirks: bool,
+}
+
+impl Dispatcher {
+    pub fn new(
+        keymap: &Keymap,
+        rom: PathBuf,
+        window_requests: Rc<WindowRequests>,
+        volume: Rc<VolumeControl>,
+        auto_quirks: bool,
+    ) -> Self {
+        Self {
+            actions: Actions::new(keymap),
+            commands: VecDeque::new(),
+            quit: false,
+            slot: 0,
+            rom,
+            window_requests,
+            volume,
+            keymap: None,
+            auto_quirks,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            // with a second window open, closing the game's doesn't quit by itself
+            Event::Quit { .. }
+            | Event::Window {
+                win_event: WindowEvent::Close,
+                ..
+            } => self.quit = true,
+            Event::DropFile { ref filename, .. } => self.swap_rom(PathBuf::from(filename)),
+            Event::KeyDown {
+                keycode,
+                scancode,
+                repeat,
+                ..
+            } => {
+                if let Some(action) = self.actions.get(scancode, keycode) {
+                    if !repeat || action.repeats() {
+                        self.dispatch(action);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self, action: Action) {
+        let command = match action {
+            Action::Quit => {
+                self.quit = true;
+                return;
+            }
+            Action::Fullscreen => return self.window_requests.toggle_fullscreen(),
+            Action::Screenshot => return self.window_requests.screenshot(),
+            Action::VolumeDown => return self.volume.step(false),
+            Action::VolumeUp => return self.volume.step(true),
+            Action::PreviousSlot | Action::NextSlot => {
+                let step = if action == Action::NextSlot {
+                    1
+                } else {
+                    STATE_SLOTS - 1
+                };
+                self.slot = (self.slot + step) % STATE_SLOTS;
+                println!("state slot {}", self.slot);
+                return;
+            }
+            Action::Pause => HostCommand::TogglePause,
+            Action::FrameAdvance => HostCommand::FrameAdvance,
+            Action::Reset => HostCommand::Reset,
+            Action::HardReset => HostCommand::HardReset,
+            Action::ReloadRom => {
+                if let Some(rom) = read_rom(&self.rom) {
+                    let profile = Database::lookup(&rom);
+                    self.load(rom, &profile);
+                }
+                return;
+            }
+            Action::FastForward => HostCommand::ToggleFastForward,
+            Action::SlowMotion => HostCommand::ToggleSlowMotion,
+            Action::SaveState => HostCommand::SaveState(self.slot),
+            Action::LoadState => HostCommand::LoadState(self.slot),
+        };
+
+        self.commands.push_back(command);
+    }
+
+    /// Switch to a rom dropped onto the window, with its own keymap
+    fn swap_rom(&mut self, path: PathBuf) {
+        let Some(rom) = read_rom(&path) else {
+            return;
+        };
+
+        let mut config = Config::load();
+        config.remember_rom(&path);
+        if let Err(e) = config.save() {
+            eprintln!("failed to save config: {}", e);
+        }
+
+        println!("switching to {}", path.display());
+        let profile = Database::lookup(&rom);
+        let name = path.file_stem().and_then(|stem| stem.to_str());
+        let keymap = Keymap::load(name, &profile.keys);
+        self.actions = Actions::new(&keymap);
+        self.keymap = Some(keymap);
+
+        self.rom = path;
+        self.load(rom, &profile);
+    }
+
+    /// Load `rom`, then freeze the cheats saved for it, as loading drops the
+    /// ones before
+    fn load(&mut self, rom: Vec<u8>, profile: &RomProfile) {
+        let command = self.load_command(rom, profile);
+        self.commands.push_back(command);
+        self.commands.push_back(HostCommand::SetCheats(
+            CheatFile::load().cheats(&profile.sha1),
+        ));
+    }
+
+    /// Load `rom` with the settings the database has for it, which may have
+    /// changed since it was last loaded, or with guessed quirks. The rom has to
+    /// fit in memory.
+    fn load_command(&self, rom: Vec<u8>, profile: &RomProfile) -> HostCommand {
+        self.window_requests.set_title(profile.caption());
+
+        let mut settings = profile.settings;
+        if self.auto_quirks {
+            let report = detect_quirks(&rom);
+            print!("{}", info::describe_quirks(&report));
+            settings.quirks = report.quirks();
+            settings.stack = Stack::for_variant(report.variant);
+        }
+
+        HostCommand::LoadRom { rom, settings }
+    }
+
+    /// The keymap of the rom last switched to, once
+    pub fn take_keymap(&mut self) -> Option<Keymap> {
+        self.keymap.take()
+    }
+
+    pub fn next_command(&mut self) -> Option<HostCommand> {
+        self.commands.pop_front()
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+}
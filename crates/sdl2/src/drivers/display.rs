@@ -1,58 +1,250 @@
-#![allow(clippy::needless_range_loop)]
+use std::{
+    cell::{Cell, RefCell},
+    fs::File,
+    io::{self, BufWriter},
+    path::PathBuf,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::FullscreenType;
 
-use chipmunk_backend::{DISPLAY_HEIGHT, DISPLAY_WIDTH, Vram};
+use super::debugger::{self, Debugger};
+use super::heatmap::Sdl2Heatmap;
+
+use chipmunk_backend::{
+    Brightness, Display, Machine, Persistence, Phosphor, Vram, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+};
+
+use crate::palette::{Palette, Rgb};
 
 const SCALE: usize = 10;
+// RGB24
+const BYTES_PER_PIXEL: usize = 3;
+
+/// Window changes requested through the event pump, applied by the display
+#[derive(Default)]
+pub struct WindowRequests {
+    toggle_fullscreen: Cell<bool>,
+    screenshot: Cell<bool>,
+    title: Cell<Option<String>>,
+}
+
+impl WindowRequests {
+    pub fn toggle_fullscreen(&self) {
+        self.toggle_fullscreen.set(!self.toggle_fullscreen.get());
+    }
+
+    /// Save the next presented frame as a PNG
+    pub fn screenshot(&self) {
+        self.screenshot.set(true);
+    }
+
+    pub fn set_title(&self, title: String) {
+        self.title.set(Some(title));
+    }
+}
+
+/// Writes the frame to a PNG scaled up like the window, in the current directory
+fn save_screenshot(brightness: &Brightness, palette: &Palette) -> io::Result<PathBuf> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+    let path = PathBuf::from(format!("chipmunk-{}.png", millis));
+
+    let width = DISPLAY_WIDTH * SCALE;
+    let height = DISPLAY_HEIGHT * SCALE;
+    let mut pixels = Vec::with_capacity(width * height * BYTES_PER_PIXEL);
+    for row in brightness.iter() {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|pixel| palette.shade(*pixel).bytes().repeat(SCALE))
+            .collect();
+        for _ in 0..SCALE {
+            pixels.extend_from_slice(&line);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(&path)?),
+        width as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(io::Error::other)?;
+
+    Ok(path)
+}
 
 pub struct Sdl2Display {
     canvas: Canvas<sdl2::video::Window>,
+    // one texel per emulated pixel, scaled up by the renderer on copy
+    texture: Texture,
+    palette: Palette,
+    phosphor: Phosphor,
+    requests: Rc<WindowRequests>,
+    heatmap: Option<Rc<RefCell<Sdl2Heatmap>>>,
+    debugger: Option<Rc<RefCell<Debugger>>>,
 }
 
 impl Sdl2Display {
-    pub fn new(context: &mut sdl2::Sdl) -> Self {
+    pub fn new(
+        context: &mut sdl2::Sdl,
+        palette: Palette,
+        persistence: Persistence,
+        title: &str,
+        requests: Rc<WindowRequests>,
+        heatmap: Option<Rc<RefCell<Sdl2Heatmap>>>,
+        debugger: Option<Rc<RefCell<Debugger>>>,
+    ) -> Self {
         let video_subsystem = context.video().unwrap();
 
-        let window = video_subsystem
-            .window(
-                "chipmunk",
+        let (width, height) = match debugger {
+            Some(_) => (debugger::WIDTH, debugger::HEIGHT),
+            None => (
                 (DISPLAY_WIDTH * SCALE) as u32,
                 (DISPLAY_HEIGHT * SCALE) as u32,
-            )
+            ),
+        };
+        let window = video_subsystem
+            .window(title, width, height)
             .position_centered()
+            .resizable()
             .build()
             .unwrap();
 
-        let canvas = window.into_canvas().build().unwrap();
+        let mut canvas = window.into_canvas().software().build().unwrap();
+        if debugger.is_some() {
+            // the panels are laid out at a fixed size, scaled with the window
+            canvas
+                .set_logical_size(width, height)
+                .expect("failed to set logical size");
+        }
+        let texture = canvas
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                DISPLAY_WIDTH as u32,
+                DISPLAY_HEIGHT as u32,
+            )
+            .unwrap();
+
+        Self {
+            canvas,
+            texture,
+            palette,
+            phosphor: Phosphor::new(persistence),
+            requests,
+            heatmap,
+            debugger,
+        }
+    }
 
-        Self { canvas }
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let next = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+
+        if let Err(e) = window.set_fullscreen(next) {
+            eprintln!("failed to toggle fullscreen: {}", e);
+        }
     }
 
-    // TODO: performance opts?
-    pub fn draw(&mut self, vram: Vram) {
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-        for j in 0..DISPLAY_HEIGHT {
-            for i in 0..DISPLAY_WIDTH {
-                if vram[j][i] {
-                    self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-                } else {
-                    self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    /// Largest integer multiple of the emulated resolution that fits in the window,
+    /// centered so the rest is letterboxed. With the debugger the game has a
+    /// fixed spot.
+    fn viewport(&self) -> Rect {
+        if self.debugger.is_some() {
+            let (width, height) = debugger::GAME;
+            return Rect::new(0, 0, width, height);
+        }
+
+        let (width, height) = self
+            .canvas
+            .output_size()
+            .expect("failed to get output size");
+        let scale = (width / DISPLAY_WIDTH as u32)
+            .min(height / DISPLAY_HEIGHT as u32)
+            .max(1);
+
+        let view_width = DISPLAY_WIDTH as u32 * scale;
+        let view_height = DISPLAY_HEIGHT as u32 * scale;
+
+        Rect::new(
+            (width as i32 - view_width as i32) / 2,
+            (height as i32 - view_height as i32) / 2,
+            view_width,
+            view_height,
+        )
+    }
+}
+
+impl Display for Sdl2Display {
+    fn inspect(&mut self, machine: &Machine) {
+        if let Some(debugger) = &self.debugger {
+            debugger.borrow_mut().inspect(machine);
+        }
+    }
+
+    fn draw(&mut self, vram: &Vram) {
+        if self.requests.toggle_fullscreen.take() {
+            self.toggle_fullscreen();
+        }
+        if let Some(title) = self.requests.title.take() {
+            if let Err(e) = self.canvas.window_mut().set_title(&title) {
+                eprintln!("failed to set the window title: {}", e);
+            }
+        }
+
+        let palette = &self.palette;
+        let brightness = self.phosphor.apply(vram);
+        self.texture
+            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                for (y, row) in brightness.iter().enumerate() {
+                    for (x, pixel) in row.iter().enumerate() {
+                        let color = palette.shade(*pixel).bytes();
+
+                        let offset = y * pitch + x * BYTES_PER_PIXEL;
+                        buffer[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&color);
+                    }
                 }
+            })
+            .expect("failed to update texture");
 
-                self.canvas
-                    .fill_rect(Rect::new(
-                        (i * SCALE) as i32,
-                        (j * SCALE) as i32,
-                        SCALE as u32,
-                        SCALE as u32,
-                    ))
-                    .expect("failed to draw!");
+        if self.requests.screenshot.take() {
+            match save_screenshot(brightness, palette) {
+                Ok(path) => println!("saved screenshot to {}", path.display()),
+                Err(e) => eprintln!("failed to save screenshot: {}", e),
             }
         }
 
+        // letterbox
+        let Rgb(r, g, b) = self.palette.background();
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+
+        let viewport = self.viewport();
+        self.canvas
+            .copy(&self.texture, None, viewport)
+            .expect("failed to draw!");
+
+        if let Some(debugger) = &self.debugger {
+            debugger.borrow().draw(&mut self.canvas, &self.palette);
+        }
+
         self.canvas.present();
+
+        if let Some(heatmap) = &self.heatmap {
+            heatmap.borrow_mut().present();
+        }
     }
 }
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+use crate::keymap::GamepadMap;
+
+/// A single control on a gamepad that can press a CHIP-8 key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Control {
+    Button(Button),
+    /// An analog stick pushed in the negative (false) or positive (true) direction
+    Axis(Axis, bool),
+}
+
+impl Control {
+    /// Parses SDL button names ("a", "dpup", ...) and stick directions ("leftx-")
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(axis) = name.strip_suffix('-') {
+            Axis::from_string(axis).map(|axis| Control::Axis(axis, false))
+        } else if let Some(axis) = name.strip_suffix('+') {
+            Axis::from_string(axis).map(|axis| Control::Axis(axis, true))
+        } else {
+            Button::from_string(name).map(Control::Button)
+        }
+    }
+}
+
+/// Game controllers through SDL's GameController subsystem. Controllers are
+/// opened as they get plugged in, including the ones present at startup.
+pub struct Sdl2Gamepad {
+    subsystem: Option<GameControllerSubsystem>,
+    // keyed by joystick instance id
+    controllers: HashMap<u32, GameController>,
+    bindings: HashMap<Control, u8>,
+    deadzone: i16,
+    held: HashMap<(u32, Control), u8>,
+}
+
+fn bindings(map: &GamepadMap) -> HashMap<Control, u8> {
+    map.bindings
+        .iter()
+        .enumerate()
+        .flat_map(|(key, names)| names.iter().map(move |name| (key as u8, name)))
+        .filter_map(|(key, name)| match Control::from_name(name) {
+            Some(control) => Some((control, key)),
+            None => {
+                eprintln!("unknown gamepad control '{}' in keymap", name);
+                None
+            }
+        })
+        .collect()
+}
+
+impl Sdl2Gamepad {
+    pub fn new(context: &mut sdl2::Sdl, map: &GamepadMap) -> Self {
+        let subsystem = context
+            .game_controller()
+            .map_err(|e| eprintln!("gamepads are unavailable: {}", e))
+            .ok();
+
+        Self {
+            subsystem,
+            controllers: HashMap::new(),
+            bindings: bindings(map),
+            deadzone: (map.deadzone * i16::MAX as f32) as i16,
+            held: HashMap::new(),
+        }
+    }
+
+    /// Switch to the bindings of another rom, keeping the controllers open
+    pub fn set_map(&mut self, map: &GamepadMap) {
+        self.bindings = bindings(map);
+        self.deadzone = (map.deadzone * i16::MAX as f32) as i16;
+        self.held.clear();
+    }
+
+    fn press(&mut self, which: u32, control: Control) {
+        if let Some(key) = self.bindings.get(&control) {
+            self.held.insert((which, control), *key);
+        }
+    }
+
+    fn release(&mut self, which: u32, control: Control) {
+        self.held.remove(&(which, control));
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let Some(subsystem) = &self.subsystem else {
+                    return;
+                };
+
+                match subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("gamepad connected: {}", controller.name());
+                        self.controllers
+                            .insert(controller.instance_id(), controller);
+                    }
+                    Err(e) => eprintln!("failed to open gamepad: {}", e),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.controllers.remove(&which) {
+                    println!("gamepad disconnected: {}", controller.name());
+                }
+                self.held.retain(|(id, _), _| *id != which);
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.press(which, Control::Button(button))
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.release(which, Control::Button(button))
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let negative = Control::Axis(axis, false);
+                let positive = Control::Axis(axis, true);
+
+                if value > self.deadzone {
+                    self.release(which, negative);
+                    self.press(which, positive);
+                } else if value < -self.deadzone {
+                    self.release(which, positive);
+                    self.press(which, negative);
+                } else {
+                    self.release(which, negative);
+                    self.release(which, positive);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The CHIP-8 key a button press event presses, if any
+    pub fn key_pressed_by(&self, event: &Event) -> Option<u8> {
+        match *event {
+            Event::ControllerButtonDown { button, .. } => {
+                self.bindings.get(&Control::Button(button)).copied()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_pressed(&self, key: &u8) -> bool {
+        self.held.values().any(|held| held == key)
+    }
+}
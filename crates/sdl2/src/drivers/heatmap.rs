@@ -0,0 +1,142 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chipmunk_backend::MEMORY_SIZE;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use super::menu::{draw_text, TEXT_SCALE};
+use crate::font::GLYPH_HEIGHT;
+use crate::heat::Heat;
+
+// One cell per address, 64 to a row
+const COLUMNS: usize = 64;
+const ROWS: usize = MEMORY_SIZE / COLUMNS;
+const CELL: u32 = 8;
+const MARGIN: i32 = 4;
+const GRID_WIDTH: u32 = COLUMNS as u32 * CELL;
+const GRID_HEIGHT: u32 = ROWS as u32 * CELL;
+// room for a line of hover text under the grid
+const HEIGHT: u32 = GRID_HEIGHT + (GLYPH_HEIGHT as u32 * TEXT_SCALE as u32) + 2 * MARGIN as u32;
+const BYTES_PER_PIXEL: usize = 3;
+const HINT: &str = "GREEN: RUN  BLUE: READ  RED: WRITTEN";
+
+/// A second window showing the memory as a grid, lit up where the rom runs,
+/// reads and writes
+pub struct Sdl2Heatmap {
+    canvas: Canvas<Window>,
+    texture: Texture,
+    heat: Rc<RefCell<Heat>>,
+    hover: Option<u16>,
+}
+
+impl Sdl2Heatmap {
+    pub fn new(context: &mut sdl2::Sdl, heat: Rc<RefCell<Heat>>) -> Self {
+        let video_subsystem = context.video().unwrap();
+        let window = video_subsystem
+            .window("chipmunk - memory", GRID_WIDTH, HEIGHT)
+            .resizable()
+            .build()
+            .unwrap();
+
+        let mut canvas = window.into_canvas().software().build().unwrap();
+        canvas
+            .set_logical_size(GRID_WIDTH, HEIGHT)
+            .expect("failed to set logical size");
+        let texture = canvas
+            .create_texture_streaming(PixelFormatEnum::RGB24, COLUMNS as u32, ROWS as u32)
+            .unwrap();
+
+        Self {
+            canvas,
+            texture,
+            heat,
+            hover: None,
+        }
+    }
+
+    /// Handle an event if it is meant for this window, telling whether it was.
+    /// Keys are left to the keypad, so the game can still be played with this
+    /// window focused.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        let own_window = self.canvas.window().id();
+        match *event {
+            Event::MouseMotion {
+                window_id, x, y, ..
+            } if window_id == own_window => {
+                let (column, row) = (x / CELL as i32, y / CELL as i32);
+                self.hover = ((0..COLUMNS as i32).contains(&column)
+                    && (0..ROWS as i32).contains(&row))
+                .then(|| (row as usize * COLUMNS + column as usize) as u16);
+                true
+            }
+            Event::MouseButtonDown { window_id, .. }
+            | Event::MouseButtonUp { window_id, .. }
+            | Event::MouseWheel { window_id, .. }
+                if window_id == own_window =>
+            {
+                true
+            }
+            Event::Window {
+                window_id,
+                win_event,
+                ..
+            } if window_id == own_window => {
+                match win_event {
+                    WindowEvent::Leave => self.hover = None,
+                    WindowEvent::Close => self.canvas.window_mut().hide(),
+                    _ => {}
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Draw the heat of the frame that just ran, then let it cool
+    pub fn present(&mut self) {
+        let mut heat = self.heat.borrow_mut();
+        self.texture
+            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                for address in 0..MEMORY_SIZE {
+                    let (x, y) = (address % COLUMNS, address / COLUMNS);
+                    let offset = y * pitch + x * BYTES_PER_PIXEL;
+                    buffer[offset..offset + BYTES_PER_PIXEL]
+                        .copy_from_slice(&heat.color(address as u16).bytes());
+                }
+            })
+            .expect("failed to update texture");
+
+        self.canvas.set_draw_color(Color::BLACK);
+        self.canvas.clear();
+        self.canvas
+            .copy(
+                &self.texture,
+                None,
+                Rect::new(0, 0, GRID_WIDTH, GRID_HEIGHT),
+            )
+            .expect("failed to draw!");
+
+        let text = match self.hover {
+            Some(address) => {
+                let (x, y) = (address as usize % COLUMNS, address as usize / COLUMNS);
+                self.canvas.set_draw_color(Color::WHITE);
+                let _ = self.canvas.draw_rect(Rect::new(
+                    x as i32 * CELL as i32,
+                    y as i32 * CELL as i32,
+                    CELL,
+                    CELL,
+                ));
+                heat.describe(address)
+            }
+            None => HINT.to_string(),
+        };
+        let y = GRID_HEIGHT as i32 + MARGIN;
+        draw_text(&mut self.canvas, &text, MARGIN, y, Color::WHITE);
+
+        self.canvas.present();
+        heat.cool();
+    }
+}
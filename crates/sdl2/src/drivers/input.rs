@@ -0,0 +1,118 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chipmunk_backend::{EmulatorError, HostCommand, HostControl, Input};
+use sdl2::EventPump;
+
+use super::{
+    debugger::Debugger, dispatcher::Dispatcher, gamepad::Sdl2Gamepad, heatmap::Sdl2Heatmap,
+    keyboard::Sdl2Keyboard,
+};
+use crate::keymap::Keymap;
+
+/// Owns the SDL event pump, so it provides both keypad input and host control.
+/// Every event goes to the keyboard, the gamepads, the hotkey dispatcher and
+/// the debugger, except for those of the memory window, which handles its own.
+pub struct Sdl2Input {
+    event_pump: EventPump,
+    keyboard: Sdl2Keyboard,
+    gamepad: Sdl2Gamepad,
+    dispatcher: Dispatcher,
+    heatmap: Option<Rc<RefCell<Sdl2Heatmap>>>,
+    debugger: Option<Rc<RefCell<Debugger>>>,
+}
+
+impl Sdl2Input {
+    pub fn new(
+        context: &mut sdl2::Sdl,
+        keymap: &Keymap,
+        dispatcher: Dispatcher,
+        heatmap: Option<Rc<RefCell<Sdl2Heatmap>>>,
+        debugger: Option<Rc<RefCell<Debugger>>>,
+    ) -> Self {
+        let event_pump = context.event_pump().unwrap();
+        let keyboard = Sdl2Keyboard::new(keymap);
+        let gamepad = Sdl2Gamepad::new(context, &keymap.gamepad);
+
+        Self {
+            event_pump,
+            keyboard,
+            gamepad,
+            dispatcher,
+            heatmap,
+            debugger,
+        }
+    }
+
+    /// Pick up the keymap of a rom the dispatcher switched to
+    fn switch_keymap(&mut self) {
+        if let Some(keymap) = self.dispatcher.take_keymap() {
+            self.keyboard.set_keymap(&keymap);
+            self.gamepad.set_map(&keymap.gamepad);
+        }
+    }
+}
+
+impl Input for Sdl2Input {
+    fn scan_keys(&mut self) {
+        for event in self.event_pump.poll_iter() {
+            if let Some(heatmap) = &self.heatmap {
+                if heatmap.borrow_mut().handle_event(&event) {
+                    continue;
+                }
+            }
+            if let Some(debugger) = &self.debugger {
+                debugger.borrow_mut().handle_event(&event);
+            }
+            self.dispatcher.handle_event(&event);
+            self.keyboard.handle_event(&event);
+            self.gamepad.handle_event(&event);
+        }
+        self.switch_keymap();
+    }
+
+    fn key_is_pressed(&self, key: &u8) -> bool {
+        self.keyboard.is_pressed(key) || self.gamepad.is_pressed(key)
+    }
+
+    fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
+        loop {
+            let event = self.event_pump.wait_event();
+
+            // hotkeys keep working, and gamepads can be plugged in, while we wait
+            self.dispatcher.handle_event(&event);
+            self.switch_keymap();
+            self.gamepad.handle_event(&event);
+            if self.dispatcher.quit_requested() {
+                return Err(EmulatorError::Exit);
+            }
+
+            let key = self
+                .keyboard
+                .key_pressed_by(&event)
+                .or_else(|| self.gamepad.key_pressed_by(&event));
+            if let Some(key) = key {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+impl HostControl for Sdl2Input {
+    fn should_quit(&mut self) -> bool {
+        self.dispatcher.quit_requested()
+    }
+
+    fn next_command(&mut self) -> Option<HostCommand> {
+        if let Some(command) = self.dispatcher.next_command() {
+            // the emulator drops its breakpoints with the old rom
+            if let (HostCommand::LoadRom { .. }, Some(debugger)) = (&command, &self.debugger) {
+                debugger.borrow_mut().rom_changed();
+            }
+            return Some(command);
+        }
+
+        self.debugger
+            .as_ref()
+            .and_then(|debugger| debugger.borrow_mut().next_command())
+    }
+}
@@ -1,93 +1,111 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
-use chipmunk_backend::EmulatorError;
-use sdl2::keyboard::Keycode;
-use sdl2::{event::Event, EventPump};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Scancode};
 
-const ESCAPE_KEY: u8 = 0xFF;
+use crate::keymap::{Keymap, Layout};
 
-fn map_keycode(code: Option<Keycode>) -> Option<u8> {
-    match code {
-        Some(Keycode::Escape) => Some(ESCAPE_KEY),
-        Some(Keycode::Num1) => Some(0x1),
-        Some(Keycode::Num2) => Some(0x2),
-        Some(Keycode::Num3) => Some(0x3),
-        Some(Keycode::Num4) => Some(0xC),
-        Some(Keycode::Q) => Some(0x4),
-        Some(Keycode::W) => Some(0x5),
-        Some(Keycode::E) => Some(0x6),
-        Some(Keycode::R) => Some(0xD),
-        Some(Keycode::A) => Some(0x7),
-        Some(Keycode::S) => Some(0x8),
-        Some(Keycode::D) => Some(0x9),
-        Some(Keycode::F) => Some(0xE),
-        Some(Keycode::Z) => Some(0xA),
-        Some(Keycode::X) => Some(0x0),
-        Some(Keycode::C) => Some(0xB),
-        Some(Keycode::V) => Some(0xF),
-        _ => None,
+/// Host key to CHIP-8 key, resolved from the names in a [`Keymap`]
+enum Lookup {
+    Physical(HashMap<Scancode, u8>),
+    Logical(HashMap<Keycode, u8>),
+}
+
+impl Lookup {
+    fn new(keymap: &Keymap) -> Self {
+        let bindings = keymap
+            .bindings
+            .iter()
+            .enumerate()
+            .flat_map(|(key, names)| names.iter().map(move |name| (key as u8, name)));
+
+        match keymap.layout {
+            Layout::Physical => Lookup::Physical(
+                bindings
+                    .filter_map(|(key, name)| match Scancode::from_name(name) {
+                        Some(scancode) => Some((scancode, key)),
+                        None => {
+                            eprintln!("unknown key '{}' in keymap", name);
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+            Layout::Logical => Lookup::Logical(
+                bindings
+                    .filter_map(|(key, name)| match Keycode::from_name(name) {
+                        Some(keycode) => Some((keycode, key)),
+                        None => {
+                            eprintln!("unknown key '{}' in keymap", name);
+                            None
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn get(&self, scancode: Option<Scancode>, keycode: Option<Keycode>) -> Option<u8> {
+        match self {
+            Lookup::Physical(map) => scancode.and_then(|scancode| map.get(&scancode).copied()),
+            Lookup::Logical(map) => keycode.and_then(|keycode| map.get(&keycode).copied()),
+        }
     }
 }
 
 pub struct Sdl2Keyboard {
-    pressed_keys: HashSet<u8>,
+    lookup: Lookup,
+    // Held host keys and the CHIP-8 key they press. Several host keys can press
+    // the same CHIP-8 key, which stays down until all of them are released.
+    held: HashMap<Scancode, u8>,
 }
 
 impl Sdl2Keyboard {
-    pub fn new() -> Self {
+    pub fn new(keymap: &Keymap) -> Self {
         Self {
-            pressed_keys: HashSet::with_capacity(16),
+            lookup: Lookup::new(keymap),
+            held: HashMap::with_capacity(16),
         }
     }
 
-    pub fn scan(&mut self, event_pump: &mut EventPump) {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::KeyDown { keycode, .. } => {
-                    let pressed_key = map_keycode(keycode);
-
-                    if let Some(pressed_key) = pressed_key {
-                        self.pressed_keys.insert(pressed_key);
-                    }
-                }
-                Event::KeyUp { keycode, .. } => {
-                    let pressed_key = map_keycode(keycode);
+    /// Switch to the bindings of another rom
+    pub fn set_keymap(&mut self, keymap: &Keymap) {
+        self.lookup = Lookup::new(keymap);
+        self.held.clear();
+    }
 
-                    if let Some(key) = pressed_key {
-                        if self.pressed_keys.contains(&key) {
-                            self.pressed_keys.remove(&key);
-                        }
-                    }
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyDown {
+                keycode, scancode, ..
+            } => {
+                if let (Some(pressed_key), Some(scancode)) =
+                    (self.lookup.get(*scancode, *keycode), scancode)
+                {
+                    self.held.insert(*scancode, pressed_key);
                 }
-                _ => {}
             }
+            Event::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => {
+                self.held.remove(scancode);
+            }
+            _ => {}
         }
     }
 
     pub fn is_pressed(&self, key: &u8) -> bool {
-        self.pressed_keys.contains(key)
+        self.held.values().any(|held| held == key)
     }
 
-    pub fn escape_is_pressed(&self) -> bool {
-        self.is_pressed(&0xFF)
-    }
-
-    pub fn await_keypress(&self, event_pump: &mut EventPump) -> Result<u8, EmulatorError> {
-        loop {
-            match event_pump.wait_event() {
-                Event::Quit { .. } => return Err(EmulatorError::Exit),
-                Event::KeyDown { keycode, .. } => {
-                    if let Some(keycode) = map_keycode(keycode) {
-                        // Check for escape
-                        if keycode == ESCAPE_KEY {
-                            return Err(EmulatorError::Exit);
-                        } else {
-                            return Ok(keycode);
-                        }
-                    }
-                }
-                _ => {}
-            }
+    /// The CHIP-8 key a key press event presses, if any
+    pub fn key_pressed_by(&self, event: &Event) -> Option<u8> {
+        match *event {
+            Event::KeyDown {
+                keycode, scancode, ..
+            } => self.lookup.get(scancode, keycode),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH, POINTER};
+use crate::library::RomEntry;
+use crate::palette::{Palette, Rgb};
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 320;
+const MARGIN: i32 = 8;
+pub(super) const TEXT_SCALE: i32 = 2;
+const LINE_HEIGHT: i32 = (GLYPH_HEIGHT as i32 + 3) * TEXT_SCALE;
+// lines that fit, less the one for the hints at the bottom
+const VISIBLE_LINES: usize = ((HEIGHT as i32 - 2 * MARGIN) / LINE_HEIGHT) as usize - 1;
+const COLUMNS: usize =
+    ((WIDTH as i32 - 2 * MARGIN) / ((GLYPH_WIDTH as i32 + 1) * TEXT_SCALE)) as usize;
+const HINTS: &str = "ENTER: RUN   ESC: QUIT   OR DROP A ROM HERE";
+
+enum Line {
+    Header(String),
+    Rom(RomEntry),
+}
+
+struct Menu {
+    lines: Vec<Line>,
+    // always a rom line, unless there are none
+    selected: usize,
+    scroll: usize,
+}
+
+impl Menu {
+    fn new(dir: &Path, recent: Vec<RomEntry>, roms: Vec<RomEntry>) -> Self {
+        let mut lines = Vec::new();
+        if !recent.is_empty() {
+            lines.push(Line::Header("Recent".to_string()));
+            lines.extend(recent.into_iter().map(Line::Rom));
+        }
+        if roms.is_empty() {
+            lines.push(Line::Header(format!("No roms in {}", dir.display())));
+        } else {
+            lines.push(Line::Header(format!("Roms in {}", dir.display())));
+            lines.extend(roms.into_iter().map(Line::Rom));
+        }
+
+        let mut menu = Self {
+            lines,
+            selected: 0,
+            scroll: 0,
+        };
+        menu.select(0, 1);
+        menu
+    }
+
+    fn rom(&self, line: usize) -> Option<&RomEntry> {
+        match self.lines.get(line) {
+            Some(Line::Rom(rom)) => Some(rom),
+            _ => None,
+        }
+    }
+
+    /// Select the closest rom at or after `line` in `direction`, staying put if
+    /// there isn't one
+    fn select(&mut self, line: usize, direction: isize) {
+        let mut line = line.min(self.lines.len().saturating_sub(1)) as isize;
+        while (0..self.lines.len() as isize).contains(&line) {
+            if self.rom(line as usize).is_some() {
+                self.selected = line as usize;
+                break;
+            }
+            line += direction;
+        }
+
+        // keep the selection, and the header above it, in view
+        if self.selected < self.scroll + 1 {
+            self.scroll = self.selected.saturating_sub(1);
+        } else if self.selected >= self.scroll + VISIBLE_LINES {
+            self.scroll = self.selected + 1 - VISIBLE_LINES;
+        }
+    }
+
+    fn step(&mut self, lines: isize) {
+        // a sideways scroll, which select couldn't search with
+        if lines == 0 {
+            return;
+        }
+        let direction = lines.signum();
+        let target = (self.selected as isize + lines).max(0) as usize;
+        self.select(target, direction);
+    }
+
+    /// The line drawn at `y`, if any
+    fn line_at(&self, y: i32) -> Option<usize> {
+        let row = (y - MARGIN).div_euclid(LINE_HEIGHT);
+        (0..VISIBLE_LINES as i32)
+            .contains(&row)
+            .then(|| self.scroll + row as usize)
+    }
+
+    fn draw(&self, canvas: &mut Canvas<Window>, palette: &Palette) {
+        let color = |Rgb(r, g, b): Rgb| Color::RGB(r, g, b);
+        let (background, foreground) = (color(palette.background()), color(palette.color(1)));
+
+        canvas.set_draw_color(background);
+        canvas.clear();
+
+        let visible = self.lines.iter().enumerate().skip(self.scroll);
+        for (row, (index, line)) in visible.take(VISIBLE_LINES).enumerate() {
+            let y = MARGIN + row as i32 * LINE_HEIGHT;
+            match line {
+                Line::Header(title) => draw_text(canvas, title, MARGIN, y, foreground),
+                Line::Rom(rom) => {
+                    let variant = rom.variant.to_string();
+                    let title_width = COLUMNS - 2 - variant.len() - 1;
+                    let title: String = rom.title.chars().take(title_width).collect();
+                    let text = format!("  {:<width$} {}", title, variant, width = title_width);
+
+                    if index == self.selected {
+                        canvas.set_draw_color(foreground);
+                        let highlight = Rect::new(
+                            MARGIN - 2,
+                            y - 2,
+                            WIDTH - 2 * MARGIN as u32 + 4,
+                            LINE_HEIGHT as u32,
+                        );
+                        let _ = canvas.fill_rect(highlight);
+                        draw_text(canvas, &text, MARGIN, y, background);
+                        draw_text(canvas, &POINTER.to_string(), MARGIN, y, background);
+                    } else {
+                        draw_text(canvas, &text, MARGIN, y, foreground);
+                    }
+                }
+            }
+        }
+
+        let y = MARGIN + VISIBLE_LINES as i32 * LINE_HEIGHT;
+        draw_text(canvas, HINTS, MARGIN, y, foreground);
+
+        canvas.present();
+    }
+}
+
+pub(super) fn draw_text(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, color: Color) {
+    let rects: Vec<Rect> = font::pixels(text)
+        .map(|(px, py)| {
+            Rect::new(
+                x + px as i32 * TEXT_SCALE,
+                y + py as i32 * TEXT_SCALE,
+                TEXT_SCALE as u32,
+                TEXT_SCALE as u32,
+            )
+        })
+        .collect();
+
+    canvas.set_draw_color(color);
+    let _ = canvas.fill_rects(&rects);
+}
+
+/// Show the roms in `dir`, with the recent ones on top, in a window of their own
+/// until one is picked or dropped onto it. `None` if the window is closed.
+pub fn choose_rom(
+    palette: &Palette,
+    dir: &Path,
+    recent: Vec<RomEntry>,
+    roms: Vec<RomEntry>,
+) -> Option<PathBuf> {
+    let context = sdl2::init().unwrap();
+    let video_subsystem = context.video().unwrap();
+    let window = video_subsystem
+        .window("chipmunk", WIDTH, HEIGHT)
+        .position_centered()
+        .resizable()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().software().build().unwrap();
+    canvas
+        .set_logical_size(WIDTH, HEIGHT)
+        .expect("failed to set logical size");
+    let mut event_pump = context.event_pump().unwrap();
+
+    let mut menu = Menu::new(dir, recent, roms);
+    loop {
+        menu.draw(&mut canvas, palette);
+
+        match event_pump.wait_event() {
+            Event::Quit { .. }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => return None,
+            Event::DropFile { filename, .. } => return Some(PathBuf::from(filename)),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Up => menu.step(-1),
+                Keycode::Down => menu.step(1),
+                Keycode::PageUp => menu.step(-(VISIBLE_LINES as isize)),
+                Keycode::PageDown => menu.step(VISIBLE_LINES as isize),
+                Keycode::Return | Keycode::KpEnter => {
+                    if let Some(rom) = menu.rom(menu.selected) {
+                        return Some(rom.path.clone());
+                    }
+                }
+                _ => {}
+            },
+            Event::MouseWheel { y, .. } => menu.step(-y.signum() as isize),
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                y,
+                ..
+            } => {
+                if let Some(rom) = menu.line_at(y).and_then(|line| menu.rom(line)) {
+                    return Some(rom.path.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,75 +1,151 @@
-#![allow(clippy::needless_bool)]
-#![allow(clippy::if_same_then_else)]
-
-mod display;
 mod audio;
+mod debugger;
+mod dispatcher;
+mod display;
+mod gamepad;
+mod heatmap;
+mod input;
 mod keyboard;
+mod menu;
 
-use audio::Sdl2Audio;
-use display::Sdl2Display;
-use keyboard::Sdl2Keyboard;
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
 
-use sdl2::{event::Event, EventPump};
+use audio::{Sdl2Audio, VolumeControl};
+use debugger::Debugger;
+use dispatcher::Dispatcher;
+use display::{Sdl2Display, WindowRequests};
+use heatmap::Sdl2Heatmap;
+use input::Sdl2Input;
+pub use menu::choose_rom;
 
-use chipmunk_backend::{Vram, EmulatorError, Platform};
+use chipmunk_backend::{
+    Audio, Display, EmulatorError, HostCommand, HostControl, Input, Machine, Peripherals,
+    Persistence, Tone, Vram,
+};
+
+use crate::{heat::Heat, keymap::Keymap, palette::Palette};
+
+/// Everything the SDL2 peripherals can be configured with
+pub struct Sdl2Options {
+    /// Where the running rom came from
+    pub rom: PathBuf,
+    /// Window caption
+    pub title: String,
+    pub palette: Palette,
+    pub persistence: Persistence,
+    pub tone: Tone,
+    pub keymap: Keymap,
+    /// Show where the rom touches memory in a second window
+    pub heat: Option<Rc<RefCell<Heat>>>,
+    /// Show the debugger panels next to the game
+    pub debugger: bool,
+    /// Guess the quirks of roms dropped onto the window or reloaded, like
+    /// `--quirks auto`
+    pub auto_quirks: bool,
+}
 
 pub struct Sdl2Platform {
-    event_pump: EventPump,
-    display: display::Sdl2Display,
+    display: Sdl2Display,
     audio: Sdl2Audio,
-    keyboard: keyboard::Sdl2Keyboard,
+    input: Sdl2Input,
 }
 
 impl Sdl2Platform {
-    pub fn new() -> Self {
+    pub fn new(options: Sdl2Options) -> Self {
         let mut context = sdl2::init().unwrap();
-        let event_pump = context.event_pump().unwrap();
+        let window_requests = Rc::new(WindowRequests::default());
+        let volume = Rc::new(VolumeControl::new(options.tone.volume));
+        let heatmap = options
+            .heat
+            .map(|heat| Rc::new(RefCell::new(Sdl2Heatmap::new(&mut context, heat))));
+        let debugger = options
+            .debugger
+            .then(|| Rc::new(RefCell::new(Debugger::new())));
 
-        let display = Sdl2Display::new(&mut context);
-        let audio = Sdl2Audio::new(&mut context);
-        let keyboard = Sdl2Keyboard::new();
+        let display = Sdl2Display::new(
+            &mut context,
+            options.palette,
+            options.persistence,
+            &options.title,
+            Rc::clone(&window_requests),
+            heatmap.clone(),
+            debugger.clone(),
+        );
+        let audio = Sdl2Audio::new(&mut context, options.tone, Rc::clone(&volume));
+        let dispatcher = Dispatcher::new(
+            &options.keymap,
+            options.rom,
+            window_requests,
+            volume,
+            options.auto_quirks,
+        );
+        let input = Sdl2Input::new(&mut context, &options.keymap, dispatcher, heatmap, debugger);
 
         Self {
-            event_pump,
             display,
             audio,
-            keyboard,
+            input,
         }
     }
+
+    /// Split into independent peripherals. Input and host control share the
+    /// event pump, so they are backed by the same object.
+    pub fn into_peripherals(self) -> Peripherals {
+        let input = Rc::new(RefCell::new(self.input));
+
+        Peripherals::new(
+            Box::new(self.display),
+            Box::new(self.audio),
+            Box::new(Rc::clone(&input)),
+            Box::new(input),
+        )
+    }
 }
 
-impl Platform for Sdl2Platform {
-    fn draw(&mut self, vram: Vram) {
+impl Display for Sdl2Platform {
+    fn draw(&mut self, vram: &Vram) {
         self.display.draw(vram);
     }
 
+    fn inspect(&mut self, machine: &Machine) {
+        self.display.inspect(machine);
+    }
+}
+
+impl Audio for Sdl2Platform {
     fn start_beep(&mut self) {
-        self.audio.start();
+        self.audio.start_beep();
     }
 
     fn stop_beep(&mut self) {
-        self.audio.stop();
+        self.audio.stop_beep();
     }
 
+    fn end_frame(&mut self) {
+        self.audio.end_frame();
+    }
+}
+
+impl Input for Sdl2Platform {
     fn scan_keys(&mut self) {
-        self.keyboard.scan(&mut self.event_pump);
+        self.input.scan_keys();
     }
 
     fn key_is_pressed(&self, key: &u8) -> bool {
-        self.keyboard.is_pressed(key)
+        self.input.key_is_pressed(key)
+    }
+
+    fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
+        self.input.await_keypress()
     }
+}
 
+impl HostControl for Sdl2Platform {
     fn should_quit(&mut self) -> bool {
-        if self.keyboard.escape_is_pressed() {
-            true
-        } else if matches!(self.event_pump.poll_event(), Some(Event::Quit { .. })) {
-            true
-        } else {
-            false
-        }
+        self.input.should_quit()
     }
 
-    fn await_keypress(&mut self) -> Result<u8, EmulatorError> {
-        self.keyboard.await_keypress(&mut self.event_pump)
+    fn next_command(&mut self) -> Option<HostCommand> {
+        self.input.next_command()
     }
 }
@@ -0,0 +1,157 @@
+use chipmunk_backend::{Access, Machine, Observer, MEMORY_SIZE};
+
+use crate::palette::Rgb;
+
+// How much of its heat an address keeps from one frame to the next, out of 256
+const COOLING: u16 = 240;
+const BACKGROUND: Rgb = Rgb::hex(0x181818);
+
+/// The last instruction that touched an address, and how
+#[derive(Clone, Copy, Debug)]
+struct Touch {
+    access: Access,
+    address: u16,
+    instruction: u16,
+}
+
+/// How recently each address was executed, read and written, cooling down
+/// frame by frame. Add it to the emulator as an observer.
+pub struct Heat {
+    // per address: execute, read, write
+    heat: Vec<[u8; 3]>,
+    last: Vec<Option<Touch>>,
+    // a copy of memory, taken once per frame
+    memory: Vec<u8>,
+    stale: bool,
+    // the instruction being run, for the reads and writes it does
+    current: (u16, u16),
+}
+
+impl Default for Heat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn channel(access: Access) -> usize {
+    match access {
+        Access::Execute => 0,
+        Access::Read => 1,
+        Access::Write => 2,
+    }
+}
+
+impl Heat {
+    pub fn new() -> Self {
+        Self {
+            heat: vec![[0; 3]; MEMORY_SIZE],
+            last: vec![None; MEMORY_SIZE],
+            memory: vec![0; MEMORY_SIZE],
+            stale: true,
+            current: (0, 0),
+        }
+    }
+
+    fn touch(&mut self, address: u16, access: Access) {
+        let (from, instruction) = self.current;
+        let index = address as usize % MEMORY_SIZE;
+        self.heat[index][channel(access)] = u8::MAX;
+        self.last[index] = Some(Touch {
+            access,
+            address: from,
+            instruction,
+        });
+    }
+
+    /// Let a frame's worth of heat go
+    pub fn cool(&mut self) {
+        for heat in self.heat.iter_mut().flatten() {
+            *heat = (*heat as u16 * COOLING / 256) as u8;
+        }
+        self.stale = true;
+    }
+
+    /// Executes show green, reads blue and writes red, mixed where they overlap
+    pub fn color(&self, address: u16) -> Rgb {
+        let [execute, read, write] = self.heat[address as usize % MEMORY_SIZE];
+        let mix = |base: u8, heat: u8| base.saturating_add(heat);
+        Rgb(
+            mix(BACKGROUND.0, write),
+            mix(BACKGROUND.1, execute),
+            mix(BACKGROUND.2, read),
+        )
+    }
+
+    /// A line about `address` for the hover text
+    pub fn describe(&self, address: u16) -> String {
+        let index = address as usize % MEMORY_SIZE;
+        let mut text = format!("{:03X}: {:02X}", address, self.memory[index]);
+        if let Some(touch) = self.last[index] {
+            text.push_str(&format!(
+                "  {} by {:03X}: {:04X}",
+                touch.access.name(),
+                touch.address,
+                touch.instruction
+            ));
+        }
+        text
+    }
+}
+
+impl Observer for Heat {
+    fn before_instruction(&mut self, machine: &Machine, address: u16, instruction: u16) {
+        if self.stale {
+            self.memory.copy_from_slice(machine.memory());
+            self.stale = false;
+        }
+        self.current = (address, instruction);
+        self.touch(address, Access::Execute);
+    }
+
+    fn memory_read(&mut self, address: u16, _value: u8) {
+        self.touch(address, Access::Read);
+    }
+
+    fn memory_write(&mut self, address: u16, _old: u8, _new: u8) {
+        self.touch(address, Access::Write);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use chipmunk_backend::{Emulator, Headless, Peripherals, Settings};
+
+    use crate::heat::{Heat, BACKGROUND};
+
+    #[test]
+    fn touched_addresses_glow_and_cool_down() {
+        // I = 0x300, store V0 there, spin
+        let rom = vec![0xA3, 0x00, 0xF0, 0x55, 0x12, 0x04];
+        let heat = Rc::new(RefCell::new(Heat::new()));
+        let mut emulator = Emulator::new(
+            rom,
+            Peripherals::from_platform(Headless::new(None)),
+            Settings::default(),
+            false,
+        )
+        .unwrap();
+        emulator.add_observer(Box::new(Rc::clone(&heat)));
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+
+        let mut heat = heat.borrow_mut();
+        assert_eq!(heat.color(0x300).0, u8::MAX);
+        assert_eq!(heat.color(0x202).1, u8::MAX);
+        assert_eq!(heat.color(0x400), BACKGROUND);
+        assert_eq!(heat.describe(0x202), "202: F0  executed by 202: F055");
+        assert_eq!(heat.describe(0x300), "300: 00  written by 202: F055");
+
+        for _ in 0..100 {
+            heat.cool();
+        }
+        assert_eq!(heat.color(0x300), BACKGROUND);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Things the host keys can do besides pressing the keypad
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    Fullscreen,
+    Pause,
+    FrameAdvance,
+    Reset,
+    HardReset,
+    ReloadRom,
+    FastForward,
+    SlowMotion,
+    Screenshot,
+    SaveState,
+    LoadState,
+    PreviousSlot,
+    NextSlot,
+    VolumeDown,
+    VolumeUp,
+}
+
+impl Action {
+    /// The name used in the keymap file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Fullscreen => "fullscreen",
+            Action::Pause => "pause",
+            Action::FrameAdvance => "frame-advance",
+            Action::Reset => "reset",
+            Action::HardReset => "hard-reset",
+            Action::ReloadRom => "reload-rom",
+            Action::FastForward => "fast-forward",
+            Action::SlowMotion => "slow-motion",
+            Action::Screenshot => "screenshot",
+            Action::SaveState => "save-state",
+            Action::LoadState => "load-state",
+            Action::PreviousSlot => "previous-slot",
+            Action::NextSlot => "next-slot",
+            Action::VolumeDown => "volume-down",
+            Action::VolumeUp => "volume-up",
+        }
+    }
+
+    /// Whether holding the key down keeps repeating the action
+    pub fn repeats(self) -> bool {
+        matches!(
+            self,
+            Action::FrameAdvance | Action::VolumeDown | Action::VolumeUp
+        )
+    }
+}
+
+// Out of the way of the default keypad keys. Like the keypad, they are matched
+// by position with the physical layout, so they stay out of its way on any
+// keyboard.
+const DEFAULT_HOTKEYS: [(Action, &[&str]); 16] = [
+    (Action::Quit, &["Escape"]),
+    (Action::Fullscreen, &["F11"]),
+    (Action::Pause, &["P", "Pause"]),
+    (Action::FrameAdvance, &["N"]),
+    (Action::Reset, &["F1"]),
+    (Action::HardReset, &["F2"]),
+    (Action::ReloadRom, &["F3"]),
+    (Action::FastForward, &["Tab"]),
+    (Action::SlowMotion, &["Backspace"]),
+    (Action::Screenshot, &["F12"]),
+    (Action::SaveState, &["F5"]),
+    (Action::LoadState, &["F8"]),
+    (Action::PreviousSlot, &["F6"]),
+    (Action::NextSlot, &["F7"]),
+    (Action::VolumeDown, &["-"]),
+    (Action::VolumeUp, &["="]),
+];
+
+/// Action to the host keys (SDL keycode names) that trigger it
+pub type HotkeyBindings = BTreeMap<Action, Vec<String>>;
+
+/// The host keys bound to each action
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hotkeys {
+    pub bindings: HotkeyBindings,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        let bindings = DEFAULT_HOTKEYS
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().map(|key| key.to_string()).collect()))
+            .collect();
+
+        Self { bindings }
+    }
+}
+
+impl Hotkeys {
+    /// Replace the keys of every action listed in `overrides`
+    pub fn apply(&mut self, overrides: &HotkeyBindings) {
+        for (action, keys) in overrides {
+            self.bindings.insert(*action, keys.clone());
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        for (action, keys) in &self.bindings {
+            out.push_str(&format!("{:<15} {}\n", action.name(), keys.join("/")));
+        }
+
+        out
+    }
+}
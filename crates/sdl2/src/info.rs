@@ -0,0 +1,118 @@
+use std::fmt::{Display, Write};
+
+use chipmunk_backend::{analyze, detect_quirks, QuirkReport};
+
+use crate::database::RomProfile;
+
+/// CRC-32 as used by zip and PNG, which many rom lists are keyed by
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn list<T: Display>(items: impl IntoIterator<Item = T>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+/// One line per quirk, with the guess, how sure it is and why
+pub fn describe_quirks(report: &QuirkReport) -> String {
+    let mut out = format!("Guessed quirks, starting from {}:\n", report.variant);
+    for guess in &report.guesses {
+        let _ = writeln!(
+            out,
+            "  {:<22}{:<4}{:>4.0}%  {}",
+            guess.quirk.name(),
+            if guess.on { "on" } else { "off" },
+            guess.confidence * 100.0,
+            guess.reason
+        );
+    }
+    out
+}
+
+/// A report of what the database and a static look at the code tell about `rom`
+pub fn describe(rom: &[u8], profile: &RomProfile) -> String {
+    let analysis = analyze(rom);
+
+    let mut out = String::new();
+    let mut line = |label: &str, value: String| {
+        let _ = writeln!(out, "{:<18}{}", format!("{}:", label), value);
+    };
+
+    line("Size", format!("{} bytes", rom.len()));
+    line("SHA-1", profile.sha1.clone());
+    line("CRC-32", format!("{:08x}", crc32(rom)));
+    match &profile.title {
+        Some(title) => {
+            line("Title", title.clone());
+            line("Authors", list(&profile.authors));
+        }
+        None => line("Title", "unknown, not in the rom database".to_string()),
+    }
+    if let Some(platform) = &profile.platform {
+        line("Platform", platform.clone());
+        line(
+            "Quirks",
+            list(profile.settings.quirks.enabled().map(|quirk| quirk.name())),
+        );
+        line("Tickrate", profile.settings.tickrate.to_string());
+    }
+    line("Instructions", list(&analysis.families));
+    line("Guessed platform", analysis.variant.to_string());
+    line(
+        "Writes to itself",
+        match analysis.writes_to_itself {
+            true => "probably".to_string(),
+            false => "no".to_string(),
+        },
+    );
+    line(
+        "Max stack depth",
+        match analysis.max_stack_depth {
+            Some(depth) => depth.to_string(),
+            None => "unbounded, subroutines recurse".to_string(),
+        },
+    );
+
+    out.push_str(&describe_quirks(&detect_quirks(rom)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::{sha1, Database};
+    use crate::info::{crc32, describe};
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn roms_that_stop_on_errors_can_be_described() {
+        let roms: [&[u8]; 2] = [
+            // a font character past F
+            &[0x60, 0x20, 0xF0, 0x29, 0xD0, 0x01, 0x12, 0x06],
+            // drawing past the end of memory
+            &[0xAF, 0xF8, 0xD0, 0x0F, 0x12, 0x04],
+        ];
+
+        for rom in roms {
+            let profile = Database::default().profile(&sha1(rom));
+            assert!(describe(rom, &profile).contains("Guessed quirks"));
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::database::KeyHints;
+use crate::hotkeys::{Action, HotkeyBindings, Hotkeys};
+
+const KEYMAP_FILE: &str = "keymap.toml";
+
+// CHIP-8 keys laid out like the original keypad
+const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+// The host keys in the same positions on a QWERTY keyboard
+const DEFAULT_KEYS: [[&str; 4]; 4] = [
+    ["1", "2", "3", "4"],
+    ["Q", "W", "E", "R"],
+    ["A", "S", "D", "F"],
+    ["Z", "X", "C", "V"],
+];
+
+// Directions on the D-pad and left stick to 2/4/6/8, like a numeric keypad
+const DEFAULT_GAMEPAD: [(u8, &[&str]); 8] = [
+    (0x2, &["dpup", "lefty-"]),
+    (0x4, &["dpleft", "leftx-"]),
+    (0x6, &["dpright", "leftx+"]),
+    (0x8, &["dpdown", "lefty+"]),
+    (0x5, &["a"]),
+    (0x0, &["b"]),
+    (0xA, &["x"]),
+    (0xB, &["y"]),
+];
+const DEFAULT_DEADZONE: f32 = 0.3;
+
+// The keys and gamepad controls given to the directions and buttons a rom's key
+// hints name
+const HINTED_CONTROLS: [(&str, &[&str], &[&str]); 6] = [
+    ("up", &["Up"], &["dpup", "lefty-"]),
+    ("down", &["Down"], &["dpdown", "lefty+"]),
+    ("left", &["Left"], &["dpleft", "leftx-"]),
+    ("right", &["Right"], &["dpright", "leftx+"]),
+    ("a", &["Space"], &["a"]),
+    ("b", &["Left Shift"], &["b"]),
+];
+
+/// How host key names are matched against the keyboard
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// By position (SDL scancode names), so the keypad keeps its shape on
+    /// AZERTY, Dvorak and friends
+    #[default]
+    Physical,
+    /// By the symbol printed on the key (SDL keycode names)
+    Logical,
+}
+
+/// CHIP-8 key (as a hex digit) to the host keys that press it
+type Bindings = BTreeMap<String, Vec<String>>;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct GamepadFile {
+    deadzone: Option<f32>,
+    keys: Bindings,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct RomKeymap {
+    layout: Option<Layout>,
+    keys: Bindings,
+    gamepad: GamepadFile,
+}
+
+/// Contents of `keymap.toml`
+///
+/// ```toml
+/// layout = "physical"
+///
+/// [keys]
+/// 5 = ["W", "Up"]
+///
+/// # SDL game controller button names, and stick directions like "leftx-"
+/// [gamepad]
+/// deadzone = 0.3
+///
+/// # SDL keycode names, for every rom
+/// [hotkeys]
+/// pause = ["Space"]
+///
+/// # overrides for roms/pong.rom
+/// [roms.pong.keys]
+/// 1 = ["Up"]
+/// 4 = ["Down"]
+///
+/// [roms.pong.gamepad.keys]
+/// 1 = ["dpup"]
+/// 4 = ["dpdown"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct KeymapFile {
+    layout: Layout,
+    keys: Bindings,
+    gamepad: GamepadFile,
+    hotkeys: HotkeyBindings,
+    roms: BTreeMap<String, RomKeymap>,
+}
+
+/// The gamepad controls bound to each of the 16 CHIP-8 keys
+#[derive(Clone, Debug, PartialEq)]
+pub struct GamepadMap {
+    /// How far a stick has to be pushed to count, from 0.0 to 1.0
+    pub deadzone: f32,
+    pub bindings: [Vec<String>; 16],
+}
+
+impl Default for GamepadMap {
+    fn default() -> Self {
+        let mut bindings: [Vec<String>; 16] = Default::default();
+        for (key, names) in DEFAULT_GAMEPAD {
+            bindings[key as usize] = names.iter().map(|name| name.to_string()).collect();
+        }
+
+        Self {
+            deadzone: DEFAULT_DEADZONE,
+            bindings,
+        }
+    }
+}
+
+/// The host keys and gamepad controls bound to each of the 16 CHIP-8 keys, and
+/// the hotkeys
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    pub layout: Layout,
+    pub bindings: [Vec<String>; 16],
+    pub gamepad: GamepadMap,
+    pub hotkeys: Hotkeys,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings: [Vec<String>; 16] = Default::default();
+        for (keys, names) in KEYPAD.iter().zip(DEFAULT_KEYS.iter()) {
+            for (key, name) in keys.iter().zip(names.iter()) {
+                bindings[*key as usize] = vec![name.to_string()];
+            }
+        }
+
+        Self {
+            layout: Layout::default(),
+            bindings,
+            gamepad: GamepadMap::default(),
+            hotkeys: Hotkeys::default(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join(KEYMAP_FILE))
+    }
+
+    /// Load the keymap file, applying the key hints from the rom database and then
+    /// the overrides for `rom` (the rom's file name without extension) if there
+    /// are any
+    pub fn load(rom: Option<&str>, hints: &KeyHints) -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default().with_hints(hints);
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents, rom, hints).unwrap_or_else(|e| {
+                eprintln!("ignoring invalid keymap {}: {}", path.display(), e);
+                Self::default().with_hints(hints)
+            }),
+            Err(_) => Self::default().with_hints(hints),
+        }
+    }
+
+    fn parse(contents: &str, rom: Option<&str>, hints: &KeyHints) -> Result<Self, String> {
+        let file: KeymapFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        let mut keymap = Self {
+            layout: file.layout,
+            ..Self::default()
+        };
+        apply(&mut keymap.bindings, &file.keys)?;
+        keymap.gamepad.apply(&file.gamepad)?;
+        keymap.hotkeys.apply(&file.hotkeys);
+        keymap = keymap.with_hints(hints);
+
+        if let Some(overrides) = rom.and_then(|rom| file.roms.get(rom)) {
+            keymap.layout = overrides.layout.unwrap_or(keymap.layout);
+            apply(&mut keymap.bindings, &overrides.keys)?;
+            keymap.gamepad.apply(&overrides.gamepad)?;
+        }
+
+        Ok(keymap)
+    }
+
+    /// Move the arrow keys, D-pad and friends to the CHIP-8 keys the rom uses for
+    /// the directions and buttons in `hints`
+    fn with_hints(mut self, hints: &KeyHints) -> Self {
+        for (name, key) in hints {
+            let Some((_, keys, controls)) =
+                HINTED_CONTROLS.iter().find(|(hinted, _, _)| hinted == name)
+            else {
+                continue;
+            };
+            if *key > 0xF {
+                continue;
+            }
+
+            bind(&mut self.bindings, *key, keys);
+            bind(&mut self.gamepad.bindings, *key, controls);
+        }
+
+        self
+    }
+
+    /// Each hotkey with each of its keys, matched by [`Keymap::layout`] like the
+    /// keypad. Keys the keypad is bound to are left out, so they only press it.
+    pub fn hotkey_keys(&self) -> Vec<(Action, &str)> {
+        let keypad: Vec<&String> = self.bindings.iter().flatten().collect();
+        self.hotkeys
+            .bindings
+            .iter()
+            .flat_map(|(action, names)| names.iter().map(move |name| (*action, name.as_str())))
+            .filter(|(action, name)| {
+                let clash = keypad.iter().any(|key| key.eq_ignore_ascii_case(name));
+                if clash {
+                    eprintln!(
+                        "ignoring hotkey '{}' for {}, the keypad uses it",
+                        name,
+                        action.name()
+                    );
+                }
+                !clash
+            })
+            .collect()
+    }
+
+    /// The active layout drawn as the keypad, followed by the hotkeys
+    pub fn describe(&self) -> String {
+        format!(
+            "Keyboard ({:?} layout):\n{}\nGamepad (deadzone {}):\n{}\nHotkeys:\n{}",
+            self.layout,
+            describe_bindings(&self.bindings),
+            self.gamepad.deadzone,
+            describe_bindings(&self.gamepad.bindings),
+            self.hotkeys.describe()
+        )
+    }
+}
+
+impl GamepadMap {
+    fn apply(&mut self, file: &GamepadFile) -> Result<(), String> {
+        if let Some(deadzone) = file.deadzone {
+            if !(0.0..1.0).contains(&deadzone) {
+                return Err(format!("deadzone {} is not between 0.0 and 1.0", deadzone));
+            }
+            self.deadzone = deadzone;
+        }
+
+        apply(&mut self.bindings, &file.keys)
+    }
+}
+
+/// Replace the controls of every CHIP-8 key listed in `overrides`
+fn apply(bindings: &mut [Vec<String>; 16], overrides: &Bindings) -> Result<(), String> {
+    for (key, names) in overrides {
+        let key = u8::from_str_radix(key, 16)
+            .ok()
+            .filter(|key| *key <= 0xF)
+            .ok_or_else(|| format!("'{}' is not a CHIP-8 key (0-F)", key))?;
+
+        bindings[key as usize] = names.clone();
+    }
+
+    Ok(())
+}
+
+/// Add `names` to `key`, taking them away from any other key
+fn bind(bindings: &mut [Vec<String>; 16], key: u8, names: &[&str]) {
+    for bound in bindings.iter_mut() {
+        bound.retain(|name| !names.contains(&name.as_str()));
+    }
+
+    bindings[key as usize].extend(names.iter().map(|name| name.to_string()));
+}
+
+fn describe_bindings(bindings: &[Vec<String>; 16]) -> String {
+    let width = bindings
+        .iter()
+        .map(|names| names.join("/").len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut out = String::new();
+    for row in KEYPAD.iter() {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|key| {
+                format!(
+                    "{:X} = {:<width$}",
+                    key,
+                    bindings[*key as usize].join("/"),
+                    width = width
+                )
+            })
+            .collect();
+        out.push_str(cells.join(" | ").trim_end());
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::KeyHints;
+    use crate::hotkeys::Action;
+    use crate::keymap::{Keymap, Layout};
+
+    const FILE: &str = r#"
+        layout = "logical"
+
+        [keys]
+        5 = ["W", "Up"]
+
+        [hotkeys]
+        pause = ["Space"]
+
+        [roms.pong]
+        layout = "physical"
+
+        [roms.pong.keys]
+        c = ["Down"]
+
+        [roms.pong.gamepad]
+        deadzone = 0.5
+
+        [roms.pong.gamepad.keys]
+        1 = ["dpup"]
+    "#;
+
+    #[test]
+    fn defaults_to_qwerty_positions() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.layout, Layout::Physical);
+        assert_eq!(keymap.bindings[0x0], vec!["X"]);
+        assert_eq!(keymap.bindings[0xC], vec!["4"]);
+    }
+
+    #[test]
+    fn file_overrides_defaults() {
+        let keymap = Keymap::parse(FILE, None, &KeyHints::new()).unwrap();
+
+        assert_eq!(keymap.layout, Layout::Logical);
+        assert_eq!(keymap.bindings[0x5], vec!["W", "Up"]);
+        assert_eq!(keymap.bindings[0xC], vec!["4"]);
+    }
+
+    #[test]
+    fn rom_overrides_file() {
+        let keymap = Keymap::parse(FILE, Some("pong"), &KeyHints::new()).unwrap();
+
+        assert_eq!(keymap.layout, Layout::Physical);
+        assert_eq!(keymap.bindings[0x5], vec!["W", "Up"]);
+        assert_eq!(keymap.bindings[0xC], vec!["Down"]);
+    }
+
+    #[test]
+    fn gamepad_is_per_rom() {
+        let keymap = Keymap::parse(FILE, None, &KeyHints::new()).unwrap();
+        assert_eq!(keymap.gamepad.bindings[0x2], vec!["dpup", "lefty-"]);
+        assert!(keymap.gamepad.bindings[0x1].is_empty());
+
+        let pong = Keymap::parse(FILE, Some("pong"), &KeyHints::new()).unwrap();
+        assert_eq!(pong.gamepad.deadzone, 0.5);
+        assert_eq!(pong.gamepad.bindings[0x1], vec!["dpup"]);
+    }
+
+    #[test]
+    fn hotkeys_can_be_rebound() {
+        let keymap = Keymap::parse(FILE, None, &KeyHints::new()).unwrap();
+
+        assert_eq!(keymap.hotkeys.bindings[&Action::Pause], vec!["Space"]);
+        assert_eq!(keymap.hotkeys.bindings[&Action::Quit], vec!["Escape"]);
+        assert!(Keymap::parse("[hotkeys]\nexplode = [\"X\"]", None, &KeyHints::new()).is_err());
+    }
+
+    #[test]
+    fn hotkeys_on_keypad_keys_are_left_out() {
+        let keymap = Keymap::default();
+        assert!(keymap.hotkey_keys().contains(&(Action::Pause, "P")));
+
+        // R is keypad D by position
+        let keymap = Keymap::parse(
+            "[hotkeys]\npause = [\"R\", \"Pause\"]",
+            None,
+            &KeyHints::new(),
+        )
+        .unwrap();
+        let pause: Vec<&str> = keymap
+            .hotkey_keys()
+            .into_iter()
+            .filter(|(action, _)| *action == Action::Pause)
+            .map(|(_, key)| key)
+            .collect();
+        assert_eq!(pause, ["Pause"]);
+    }
+
+    #[test]
+    fn key_hints_come_before_rom_overrides() {
+        let hints = KeyHints::from([("up".to_string(), 0x1), ("a".to_string(), 0x2)]);
+
+        let keymap = Keymap::parse(FILE, None, &hints).unwrap();
+        assert_eq!(keymap.bindings[0x1], vec!["1", "Up"]);
+        assert_eq!(keymap.bindings[0x5], vec!["W"]);
+        assert_eq!(keymap.gamepad.bindings[0x2], vec!["a"]);
+        assert_eq!(keymap.gamepad.bindings[0x1], vec!["dpup", "lefty-"]);
+
+        let pong = Keymap::parse(FILE, Some("pong"), &hints).unwrap();
+        assert_eq!(pong.gamepad.bindings[0x1], vec!["dpup"]);
+    }
+
+    #[test]
+    fn rejects_unknown_chip8_keys() {
+        assert!(Keymap::parse("[keys]\n10 = [\"Q\"]", None, &KeyHints::new()).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chipmunk_backend::{detect_variant, Variant, MAX_ROM_SIZE};
+
+use crate::database::{platform_variant, sha1, Database};
+
+const ROM_EXTENSIONS: [&str; 2] = ["ch8", "rom"];
+
+/// A rom file, with what could be found out about it for listing
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub title: String,
+    pub variant: Variant,
+}
+
+/// Read a rom, which has to fit in memory
+pub fn read_rom(path: &Path) -> io::Result<Vec<u8>> {
+    let rom = fs::read(path)?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "it is {} bytes and at most {} fit in memory",
+                rom.len(),
+                MAX_ROM_SIZE
+            ),
+        ));
+    }
+
+    Ok(rom)
+}
+
+impl RomEntry {
+    /// Title and platform from `database` if it knows the rom, from the file
+    /// name and a look at the code otherwise
+    pub fn load(path: &Path, database: &Database) -> io::Result<Self> {
+        let rom = read_rom(path)?;
+        let profile = database.profile(&sha1(&rom));
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            title: profile.title.unwrap_or_else(|| title_from_path(path)),
+            variant: profile
+                .platform
+                .as_deref()
+                .and_then(platform_variant)
+                .unwrap_or_else(|| detect_variant(&rom)),
+        })
+    }
+}
+
+fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|rom| extension.eq_ignore_ascii_case(rom))
+        })
+}
+
+/// "game-of-life.rom" becomes "game of life"
+fn title_from_path(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().replace(['-', '_'], " "))
+        .unwrap_or_default()
+}
+
+/// The roms in `dir`, sorted by file name
+pub fn scan(dir: &Path, database: &Database) -> io::Result<Vec<RomEntry>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_rom(path))
+        .collect();
+    paths.sort();
+
+    Ok(paths
+        .iter()
+        .filter_map(|path| RomEntry::load(path, database).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use chipmunk_backend::MAX_ROM_SIZE;
+
+    use crate::library::{is_rom, read_rom, title_from_path};
+
+    #[test]
+    fn only_lists_rom_extensions() {
+        assert!(is_rom(Path::new("roms/pong.rom")));
+        assert!(is_rom(Path::new("roms/octojam.CH8")));
+        assert!(!is_rom(Path::new("roms/readme.txt")));
+        assert!(!is_rom(Path::new("roms/rom")));
+    }
+
+    #[test]
+    fn titles_come_from_the_file_name() {
+        assert_eq!(
+            title_from_path(Path::new("roms/game-of-life.rom")),
+            "game of life"
+        );
+    }
+
+    #[test]
+    fn roms_too_large_for_memory_are_not_read() {
+        let path = std::env::temp_dir().join(format!("chipmunk-large-{}.ch8", std::process::id()));
+        fs::write(&path, vec![0; MAX_ROM_SIZE + 1]).unwrap();
+        let read = read_rom(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(read.is_err());
+    }
+}
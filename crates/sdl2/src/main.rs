@@ -1,33 +1,175 @@
-use clap::{Parser, Subcommand};
-use drivers::Sdl2Platform;
+use cheats::{CheatConsole, CheatFile};
+use chipmunk_backend::{
+    detect_quirks, AudioRecorder, Coverage, Emulator, Headless, Peripherals, Persistence, Stack,
+    Tone, Waveform,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use config::Config;
+use database::Database;
+use drivers::{choose_rom, Sdl2Options, Sdl2Platform};
+use heat::Heat;
+use keymap::Keymap;
+use library::RomEntry;
+use palette::{PaletteName, Rgb};
 use std::{
+    cell::RefCell,
     fs::File,
     io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
+mod cheats;
+mod config;
+mod coverage;
+mod crash;
+mod database;
 mod drivers;
+mod font;
+mod heat;
+mod hotkeys;
+mod info;
+mod keymap;
+mod library;
+mod palette;
+
+const DEFAULT_ROM_DIR: &str = "roms";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
+    /// Without a command, pick a rom to run from a menu
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Run a rom
-    Run {
-        rom: String,
-        #[arg(
-            short,
-            long,
-            help = "Enable debugging (waits for 'f' keypress between cycles)"
-        )]
-        debug: bool,
+    Run(RunArgs),
+    /// Pick a rom to run from a menu
+    Browse {
+        #[arg(default_value = DEFAULT_ROM_DIR, help = "Directory to list roms from")]
+        dir: PathBuf,
     },
     /// Disassemble a rom for debugging
     Dis { rom: String },
+    /// Print what is known about a rom: hashes, database entry and a look at the code
+    Info { rom: String },
+    /// Print the active keyboard layout and hotkeys
+    Keys {
+        #[arg(help = "Include the overrides for this rom")]
+        rom: Option<String>,
+    },
+}
+
+/// Where the quirks a rom runs with come from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum QuirkSource {
+    /// The rom database, or the defaults for unknown roms
+    #[default]
+    Database,
+    /// Guessed from the rom's code and a short trial run
+    Auto,
+}
+
+#[derive(Parser, Debug)]
+struct RunArgs {
+    rom: String,
+    #[arg(
+        short,
+        long,
+        help = "Enable debugging (waits for 'f' keypress between cycles)"
+    )]
+    debug: bool,
+    #[arg(long, value_enum, help = "Color palette, remembered for later runs")]
+    palette: Option<PaletteName>,
+    #[arg(
+        long,
+        help = "Custom foreground color (#RRGGBB), remembered for later runs"
+    )]
+    fg: Option<Rgb>,
+    #[arg(
+        long,
+        help = "Custom background color (#RRGGBB), remembered for later runs"
+    )]
+    bg: Option<Rgb>,
+    #[arg(
+        long,
+        default_value_t = Persistence::Off,
+        help = "Reduce flicker: off, blend (last two frames) or decay[:frames] (fade out)"
+    )]
+    persistence: Persistence,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = QuirkSource::Database,
+        help = "Where to get the quirks from: the rom database, or guessed from the rom"
+    )]
+    quirks: QuirkSource,
+    #[arg(
+        long,
+        default_value_t = Tone::default().frequency,
+        help = "Beeper pitch in Hz"
+    )]
+    frequency: f32,
+    #[arg(
+        long,
+        default_value_t = Tone::default().volume,
+        help = "Beeper volume, from 0.0 to 1.0"
+    )]
+    volume: f32,
+    #[arg(
+        long,
+        default_value_t = Waveform::default(),
+        help = "Beeper waveform: square, triangle or sine"
+    )]
+    waveform: Waveform,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Record the beeper to a WAV file, in step with the emulated frames"
+    )]
+    record_audio: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Run without a window, sound or keyboard, as fast as possible"
+    )]
+    headless: bool,
+    #[arg(
+        long,
+        requires = "headless",
+        help = "Stop after this many frames (60 per second)"
+    )]
+    frames: Option<u64>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "On exit, write the addresses executed, read and written to a JSON file, and a memory map PNG next to it"
+    )]
+    coverage: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with = "headless",
+        help = "Show the memory in a second window, lit up where the rom runs, reads and writes"
+    )]
+    heatmap: bool,
+    #[arg(
+        long,
+        conflicts_with = "headless",
+        help = "Show registers, stack, disassembly with breakpoints and memory next to the game"
+    )]
+    debugger: bool,
+    #[arg(
+        long,
+        help = "Search memory and freeze values by typing commands into the terminal"
+    )]
+    cheats: bool,
+    #[arg(
+        long,
+        help = "Stop with a diagnostic when the rom does something that only works on some interpreters"
+    )]
+    strict: bool,
 }
 
 fn open_rom(path: String) -> io::Result<Vec<u8>> {
@@ -38,22 +180,176 @@ fn open_rom(path: String) -> io::Result<Vec<u8>> {
     Ok(buffer)
 }
 
+/// Per rom settings are keyed by the rom's file name, without extension
+fn rom_name(path: &str) -> Option<&str> {
+    Path::new(path).file_stem().and_then(|stem| stem.to_str())
+}
+
+fn run(args: RunArgs) -> io::Result<()> {
+    let rom_path = PathBuf::from(&args.rom);
+    let name = rom_name(&args.rom).map(str::to_string);
+    let buffer = library::read_rom(&rom_path)?;
+
+    let profile = Database::lookup(&buffer);
+    if let Some(title) = &profile.title {
+        match &profile.platform {
+            Some(platform) => println!("{} ({})", title, platform),
+            None => println!("{}", title),
+        }
+    }
+    let keymap = Keymap::load(name.as_deref(), &profile.keys);
+    let mut settings = profile.settings;
+    if args.quirks == QuirkSource::Auto {
+        let report = detect_quirks(&buffer);
+        print!("{}", info::describe_quirks(&report));
+        settings.quirks = report.quirks();
+        settings.stack = Stack::for_variant(report.variant);
+    }
+    let custom_colors = args.palette.is_some() || args.fg.is_some() || args.bg.is_some();
+
+    let mut config = Config::load();
+    // picking a palette drops any custom colors from before
+    if let Some(palette) = args.palette {
+        config.display.palette = palette;
+        config.display.fg = None;
+        config.display.bg = None;
+    }
+    config.display.fg = args.fg.or(config.display.fg);
+    config.display.bg = args.bg.or(config.display.bg);
+    if !args.headless {
+        config.remember_rom(&rom_path);
+    }
+
+    if let Err(e) = config.save() {
+        eprintln!("failed to save config: {}", e);
+    }
+
+    let tone = Tone {
+        frequency: args.frequency,
+        volume: args.volume,
+        waveform: args.waveform,
+    };
+
+    let mut palette = config.display.palette();
+    // colors picked on the command line win over the rom's
+    if !custom_colors {
+        palette = palette.with_colors(&profile.colors);
+    }
+
+    let heat = args.heatmap.then(|| Rc::new(RefCell::new(Heat::new())));
+    let mut peripherals = if args.headless {
+        Peripherals::from_platform(Headless::new(args.frames))
+    } else {
+        Sdl2Platform::new(Sdl2Options {
+            rom: rom_path,
+            title: profile.caption(),
+            palette,
+            persistence: args.persistence,
+            tone,
+            keymap,
+            heat: heat.clone(),
+            debugger: args.debugger,
+            auto_quirks: args.quirks == QuirkSource::Auto,
+        })
+        .into_peripherals()
+    };
+
+    if let Some(path) = args.record_audio {
+        peripherals.audio = Box::new(AudioRecorder::create(path, tone, peripherals.audio)?);
+    }
+
+    let cheats = CheatFile::load().cheats(&profile.sha1);
+    if !cheats.is_empty() {
+        let codes: Vec<String> = cheats.iter().map(ToString::to_string).collect();
+        println!("cheats: {}", codes.join(" "));
+    }
+    if args.cheats {
+        let console = Rc::new(RefCell::new(CheatConsole::new(
+            peripherals.display,
+            peripherals.host,
+            profile.sha1.clone(),
+            cheats.clone(),
+        )));
+        peripherals.display = Box::new(Rc::clone(&console));
+        peripherals.host = Box::new(console);
+    }
+
+    let coverage = args
+        .coverage
+        .map(|path| (path, Rc::new(RefCell::new(Coverage::new()))));
+    let mut emulator = Emulator::new(buffer.clone(), peripherals, settings, args.debug)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+    emulator.set_cheats(cheats);
+    emulator.set_strict(args.strict);
+    emulator.set_crash_handler(Box::new(move |crash| match crash::save(crash, &palette) {
+        Ok(dir) => eprintln!("wrote a crash report to {}", dir.display()),
+        Err(e) => eprintln!("failed to write a crash report: {}", e),
+    }));
+    if let Some((_, coverage)) = &coverage {
+        emulator.add_observer(Box::new(Rc::clone(coverage)));
+    }
+    if let Some(heat) = heat {
+        emulator.add_observer(Box::new(heat));
+    }
+    emulator.start();
+
+    if let Some((path, coverage)) = coverage {
+        let map = coverage::save(&path, &coverage.borrow(), &buffer)?;
+        println!("wrote coverage to {} and {}", path.display(), map.display());
+    }
+
+    Ok(())
+}
+
+fn browse(dir: &Path) -> io::Result<()> {
+    let config = Config::load();
+    let database = Database::load();
+    let recent = config
+        .recent
+        .iter()
+        .filter_map(|path| RomEntry::load(path, &database).ok())
+        .collect();
+    let roms = library::scan(dir, &database).unwrap_or_else(|e| {
+        eprintln!("failed to list {}: {}", dir.display(), e);
+        Vec::new()
+    });
+
+    match choose_rom(&config.display.palette(), dir, recent, roms) {
+        Some(rom) => run(RunArgs::parse_from(["run".as_ref(), rom.as_os_str()])),
+        None => Ok(()),
+    }
+}
+
 fn main() -> io::Result<()> {
     let args = Args::parse();
 
     match args.command {
-        Commands::Run { rom, debug } => {
-            let buffer = open_rom(rom)?;
-            let platform = Box::new(Sdl2Platform::new());
-            chipmunk_backend::run(buffer, platform, debug)
-        }
-        Commands::Dis { rom } => {
+        Some(Commands::Run(args)) => run(args)?,
+        Some(Commands::Browse { dir }) => browse(&dir)?,
+        None => browse(Path::new(DEFAULT_ROM_DIR))?,
+        Some(Commands::Dis { rom }) => {
             let buffer = open_rom(rom)?;
             match chipmunk_backend::disassemble(buffer) {
                 Ok(ops) => println!("{:#?}", ops),
                 Err(e) => eprintln!("{:#?}", e),
             }
         }
+        Some(Commands::Info { rom }) => {
+            let buffer = open_rom(rom)?;
+            print!("{}", info::describe(&buffer, &Database::lookup(&buffer)));
+        }
+        Some(Commands::Keys { rom }) => {
+            let hints = rom
+                .clone()
+                .and_then(|rom| open_rom(rom).ok())
+                .map(|rom| Database::lookup(&rom).keys)
+                .unwrap_or_default();
+            let keymap = Keymap::load(rom.as_deref().and_then(rom_name), &hints);
+            if let Some(path) = Keymap::path() {
+                println!("Keymap file: {}", path.display());
+            }
+            print!("{}", keymap.describe());
+        }
     }
     Ok(())
 }
@@ -0,0 +1,186 @@
+use std::{fmt, str::FromStr};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const fn hex(value: u32) -> Self {
+        Self((value >> 16) as u8, (value >> 8) as u8, value as u8)
+    }
+
+    pub fn bytes(self) -> [u8; 3] {
+        [self.0, self.1, self.2]
+    }
+}
+
+/// Parses `#RRGGBB` or `RRGGBB`
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("expected a color like #RRGGBB, got '{}'", s));
+        }
+
+        u32::from_str_radix(digits, 16)
+            .map(Rgb::hex)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(color: Rgb) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PaletteName {
+    #[default]
+    Classic,
+    Amber,
+    GreenPhosphor,
+    Lcd,
+    HighContrast,
+}
+
+/// Colors indexed by the value of a pixel across the bit planes:
+/// 0 = background, 1 = first plane, 2 = second plane, 3 = both planes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Rgb; 4],
+}
+
+impl Palette {
+    pub fn named(name: PaletteName) -> Self {
+        let colors = match name {
+            PaletteName::Classic => [
+                Rgb::hex(0x000000),
+                Rgb::hex(0xFFFFFF),
+                Rgb::hex(0xAAAAAA),
+                Rgb::hex(0x555555),
+            ],
+            PaletteName::Amber => [
+                Rgb::hex(0x1A0F00),
+                Rgb::hex(0xFFB000),
+                Rgb::hex(0xCC7A00),
+                Rgb::hex(0x664000),
+            ],
+            PaletteName::GreenPhosphor => [
+                Rgb::hex(0x041A04),
+                Rgb::hex(0x33FF33),
+                Rgb::hex(0x1FAA1F),
+                Rgb::hex(0x0F550F),
+            ],
+            PaletteName::Lcd => [
+                Rgb::hex(0x9BBC0F),
+                Rgb::hex(0x0F380F),
+                Rgb::hex(0x306230),
+                Rgb::hex(0x8BAC0F),
+            ],
+            PaletteName::HighContrast => [
+                Rgb::hex(0x000000),
+                Rgb::hex(0xFFFF00),
+                Rgb::hex(0x00FFFF),
+                Rgb::hex(0xFF00FF),
+            ],
+        };
+
+        Self { colors }
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    pub fn with_foreground(mut self, color: Rgb) -> Self {
+        self.colors[1] = color;
+        self
+    }
+
+    pub fn with_background(mut self, color: Rgb) -> Self {
+        self.colors[0] = color;
+        self
+    }
+
+    /// Replace the first colors with `colors`, background first
+    pub fn with_colors(mut self, colors: &[Rgb]) -> Self {
+        for (color, replacement) in self.colors.iter_mut().zip(colors) {
+            *color = *replacement;
+        }
+        self
+    }
+
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[(pixel & 0b11) as usize]
+    }
+
+    /// Blend from the background to the foreground by `brightness` (0 to 255)
+    pub fn shade(&self, brightness: u8) -> Rgb {
+        let Rgb(br, bg, bb) = self.color(0);
+        let Rgb(fr, fg, fb) = self.color(1);
+
+        let mix = |from: u8, to: u8| {
+            let from = from as u32;
+            let to = to as u32;
+            let brightness = brightness as u32;
+            ((from * (255 - brightness) + to * brightness) / 255) as u8
+        };
+
+        Rgb(mix(br, fr), mix(bg, fg), mix(bb, fb))
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::named(PaletteName::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::palette::{Palette, PaletteName, Rgb};
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!("#FFB000".parse(), Ok(Rgb(0xFF, 0xB0, 0x00)));
+        assert_eq!("0f380f".parse(), Ok(Rgb(0x0F, 0x38, 0x0F)));
+        assert!("#FFF".parse::<Rgb>().is_err());
+        assert!("#GGGGGG".parse::<Rgb>().is_err());
+        assert!("+FFFFF".parse::<Rgb>().is_err());
+    }
+
+    #[test]
+    fn shade_blends_background_into_foreground() {
+        let palette = Palette::named(PaletteName::Classic);
+
+        assert_eq!(palette.shade(0), palette.colors[0]);
+        assert_eq!(palette.shade(255), palette.colors[1]);
+        assert_eq!(palette.shade(128), Rgb(128, 128, 128));
+    }
+
+    #[test]
+    fn formats_as_hex() {
+        assert_eq!(Rgb::hex(0x33FF33).to_string(), "#33FF33");
+    }
+}